@@ -2,9 +2,11 @@ let bookmax = 200;  // 最大历史记录数量
 
 function LastRead(){this.bookList="bookList"}
 LastRead.prototype={
-    set:function(bid,uri,bookname,chaptername,author,img_url){
+    // articleid/chapterid 为混淆后的ID 登录后同步到服务器用 time 为10位时间戳
+    set:function(bid,uri,bookname,chaptername,author,img_url,articleid,chapterid,time){
         if(!(bid&&uri&&bookname&&chaptername&&author&&img_url))return;
-        const v = bid + '#' + uri + '#' + bookname + '#' + chaptername + '#' + author + '#' + img_url;
+        time = time || Math.floor(Date.now() / 1000);
+        const v = bid + '#' + uri + '#' + bookname + '#' + chaptername + '#' + author + '#' + img_url + '#' + (articleid || 0) + '#' + (chapterid || 0) + '#' + time;
         let aBooks = lastread.getBook();
         const aBid = [];
         for (i=0; i<aBooks.length;i++){aBid.push(aBooks[i][0]);}
@@ -98,6 +100,40 @@ LastRead.prototype={
             document.cookie=k+"= "+";expires="+expireDate.toGMTString();
         }
     },
+    isLogin:function(){
+        return /(^|; )ss_userid=[^;]+/.test(document.cookie);
+    },
+    // 与服务器合并记录 bids 为空时同步全部并用服务器结果覆盖本地 返回合并后的记录
+    sync:function(bids){
+        if(!this.isLogin())return Promise.resolve(null);
+        const items = [];
+        const books = this.getBook();
+        for(let i=0; i<books.length; i++){
+            const b = books[i];
+            if(bids && bids.indexOf(b[0]) === -1)continue;
+            if(!(parseInt(b[6]) > 0 && parseInt(b[7]) > 0))continue;
+            items.push({articleid: parseInt(b[6]), chapterid: parseInt(b[7]), chaptername: b[3], time: parseInt(b[8]) || 0});
+        }
+        return fetch('/history/sync', {
+            method: 'POST',
+            headers: {'Content-Type': 'application/json'},
+            body: JSON.stringify({items: items})
+        }).then(r => r.json()).then(res => {
+            if(!res.success)return null;
+            if(!bids){
+                // 服务器按时间倒序 本地按时间正序 旧格式没有ID的记录保留
+                for(let i=0; i<books.length; i++){
+                    if(parseInt(books[i][6]) > 0)this.remove(books[i][0]);
+                }
+                const list = res.data.slice(0, bookmax).reverse();
+                for(let i=0; i<list.length; i++){
+                    const b = list[i];
+                    this.set(b.info_url, b.read_url, b.articlename, b.chaptername, b.author, b.img_url, b.articleid, b.chapterid, b.time);
+                }
+            }
+            return res.data;
+        }).catch(() => null);
+    },
     removeAll:function(){
         if(!!window.localStorage){
             window.localStorage.clear();
@@ -115,12 +151,22 @@ LastRead.prototype={
 
 window.lastread = new LastRead();
 
-function removebook(k) {
+function removebook(k, articleid) {
     lastread.remove(k);
-    window.location.reload()
+    delhistory(articleid || 0, k).then(() => window.location.reload());
 }
 
 function removeall() {
     lastread.removeAll();
-    window.location.reload()
+    delhistory(0).then(() => window.location.reload());
+}
+
+// 登录后同时删除服务器上的记录 articleid 为 0 时清空
+function delhistory(articleid, k) {
+    if(!lastread.isLogin() || (k && !articleid))return Promise.resolve();
+    return fetch('/history/del', {
+        method: 'POST',
+        headers: {'Content-Type': 'application/x-www-form-urlencoded'},
+        body: 'articleid=' + articleid
+    }).catch(() => {});
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+/// 章节报错 弹出输入框填写原因后提交 根据返回内容是否success判断是否成功
+// 请求不支持json格式 只能使用x-www-form-urlencoded格式
+function report_chapter(
+    articleid,
+    chapterid,
+    reason = ""
+) {
+    if (!reason) {
+        reason = prompt("请填写报错原因（如：内容错乱、章节缺失、重复章节）", "内容错乱");
+    }
+    if (!reason || !reason.trim()) {
+        return false;
+    }
+    const formData = new URLSearchParams();
+    formData.append('articleid', articleid);
+    formData.append('chapterid', chapterid);
+    formData.append('reason', reason.trim());
+    fetch(
+        "/report",
+        {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/x-www-form-urlencoded",
+            },
+            body: formData,
+        }
+    ).then((response) => {
+        return response.json();
+    }).then((data) => {
+        if (data.success) {
+            alert("报错成功，感谢您的反馈");
+            return true;
+        } else {
+            alert(data.errors ? data.errors.join("\n") : "报错失败");
+            return false;
+        }
+    }).catch((error) => {
+        alert("报错失败");
+        return false;
+    })
+}
@@ -10,10 +10,59 @@ function validateLoginForm(form) {
     return true;
 }
 
+/// 页面里有验证码时 把 captcha 和 captcha_id 一起提交
+function append_captcha(formData) {
+    const id = document.querySelector('input[name="captcha_id"]');
+    const input = document.querySelector('input[name="captcha"]');
+    if (id && input) {
+        formData.append('captcha_id', id.value);
+        formData.append('captcha', input.value.trim());
+    }
+}
+
+/// 验证码只能用一次 提交失败后换一张
+function refresh_captcha() {
+    const img = document.querySelector('img.captcha-img');
+    const input = document.querySelector('input[name="captcha"]');
+    if (img) img.click();
+    if (input) input.value = '';
+}
+
+/// 通用表单提交 返回 {success, msg} 失败时 msg 为错误详情
+function post_user_form(url, fields) {
+    const formData = new URLSearchParams();
+    for (const [key, value] of Object.entries(fields)) {
+        formData.append(key, value);
+    }
+    append_captcha(formData);
+    return fetch(
+        url,
+        {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/x-www-form-urlencoded",
+            },
+            body: formData,
+        }
+    ).then((response) => {
+        if (!response.ok) {
+            return {success: false, errors: [`HTTP 错误：${response.status}`]};
+        }
+        return response.json();
+    }).then((data) => {
+        if (data.success) return {success: true, msg: data.msg};
+        refresh_captcha();
+        return {success: false, msg: (data.errors || [data.msg]).join("\n")};
+    }).catch((error) => {
+        return {success: false, msg: "请求失败"};
+    });
+}
+
 function post_login(username, password) {
     const formData = new URLSearchParams();
     formData.append('username', username);
     formData.append('password', password);
+    append_captcha(formData);
     // 发起注册请求
     return fetch(
         "/login",
@@ -31,7 +80,8 @@ function post_login(username, password) {
         return response.json();
     }).then((data) => {
         if (data.success) return "success";
-        else return data.errors.join("\n");
+        refresh_captcha();
+        return data.errors.join("\n");
     }).catch((error) => {
         return "登录请求失败";
     });
@@ -42,6 +92,7 @@ function post_register(username, password, email) {
     formData.append('username', username);
     formData.append('password', password);
     formData.append('email', email);
+    append_captcha(formData);
     return fetch(
         "/register",
         {
@@ -58,7 +109,8 @@ function post_register(username, password, email) {
         return response.json();
     }).then((data) => {
         if (data.success) return "success";
-        else return data.errors.join("\n");
+        refresh_captcha();
+        return data.errors.join("\n");
     }).catch((error) => {
         return "注册请求失败";
     });
@@ -141,6 +193,45 @@ function add_bookshelf(
     })
 }
 
+/// 通用请求投推荐票 成功后提示今日剩余票数
+// 如不需要默认alert提示 可以设置is_alert为false
+// 未登录时会被重定向到登录页 返回的不是json
+function add_vote(
+    articleid,
+    is_alert = true
+) {
+    const formData = new URLSearchParams();
+    formData.append('articleid', articleid);
+    fetch(
+        "/addvote",
+        {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/x-www-form-urlencoded",
+            },
+            body: formData,
+        }
+    ).then((response) => {
+        if (response.redirected) {
+            window.location.href = response.url;
+            return null;
+        }
+        return response.json();
+    }).then((data) => {
+        if (!data) return false;
+        if (data.success) {
+            if(is_alert)alert("推荐成功，今日还剩 " + data.data.remain + " 票");
+            return true;
+        } else {
+            if(is_alert)alert(data.errors ? data.errors.join("\n") : "推荐失败");
+            return false;
+        }
+    }).catch((error) => {
+        if(is_alert)alert("推荐失败");
+        return false;
+    })
+}
+
 /// 通用请求删除书架 根据返回内容是否success判断是否删除成功
 // 如不需要默认alert提示 可以设置is_alert为false
 // 请求不支持json格式 只能使用x-www-form-urlencoded格式
@@ -96,7 +96,7 @@ window.onload = function() {
         if(!get_cookie("ss_userid")) {
             linkHtml = `<a href="/login">登录</a><a href="/register">注册</a>`;
         } else {
-            linkHtml = `<a href="/bookcase">书架</a><a href="/logout">退出登录</a>`;
+            linkHtml = `<a href="/bookcase">书架<span class="bookcase-updates"></span></a><a href="/account">账号</a><a href="/logout">退出登录</a>`;
         }
         const tempContainer = document.createElement('div');
         tempContainer.innerHTML = linkHtml;
@@ -106,4 +106,17 @@ window.onload = function() {
             parent.appendChild(link);
         });
     });
+    if(get_cookie("ss_userid")) {
+        bookcase_updates();
+    }
+}
+
+/// 书架有更新的小说数 显示在页头书架链接后
+function bookcase_updates() {
+    fetch("/bookcase/updates").then(r => r.json()).then(res => {
+        if (!res.success || !res.data) return;
+        document.querySelectorAll('header .bookcase-updates').forEach(item => {
+            item.textContent = `(${res.data})`;
+        });
+    }).catch(() => {});
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+use axum::{Extension, Form};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use crate::handlers::admin::report::{join_ids, parse_ids};
+use crate::services::admin::{admin_log, AdminUser};
+use crate::services::admin_chapter::{
+    delete_chapters, get_chapter_text, get_chapters, rename_chapter, reorder_chapters, save_chapter_text, ChapterEditError,
+};
+use crate::services::json::ApiResponse;
+use crate::utils::request::client_ip;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChapterListFrom {
+    articleid: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChapterIdFrom {
+    articleid: u64,
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChapterRenameFrom {
+    articleid: u64,
+    id: u64,
+    chaptername: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChapterIdsFrom {
+    articleid: u64,
+    /// 多个ID用英文逗号分隔 重排时为全部章节的新顺序
+    ids: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChapterSaveFrom {
+    articleid: u64,
+    id: u64,
+    content: String,
+}
+
+fn edit_fail<T>(e: ChapterEditError) -> ApiResponse<T> {
+    if matches!(e, ChapterEditError::Db(_) | ChapterEditError::Io(_)) {
+        eprintln!("chapter edit error: {e:?}");
+    }
+    ApiResponse::fail("操作失败", Some(vec![e.to_string()]))
+}
+
+/// 小说的全部章节 包括分卷
+pub(crate) async fn admin_chapter_list(
+    Form(params): Form<ChapterListFrom>
+) -> impl IntoResponse {
+    match get_chapters(params.articleid).await {
+        Ok(rows) => ApiResponse::success("success", Some(rows)),
+        Err(e) => edit_fail(e.into()),
+    }
+}
+
+pub(crate) async fn admin_chapter_rename(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<ChapterRenameFrom>
+) -> impl IntoResponse {
+    let name = params.chaptername.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return ApiResponse::fail("参数校验失败", Some(vec!["章节名必须是1-100个字".to_string()]));
+    }
+    match rename_chapter(params.articleid, params.id, name).await {
+        Ok(()) => {
+            admin_log(&admin, &client_ip(&headers), "修改章节名", params.articleid, &format!("{} {}", params.id, name)).await;
+            ApiResponse::success("success", Some(String::new()))
+        }
+        Err(e) => edit_fail(e),
+    }
+}
+
+/// 调整章节顺序 传入全部章节ID
+pub(crate) async fn admin_chapter_order(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<ChapterIdsFrom>
+) -> impl IntoResponse {
+    match reorder_chapters(params.articleid, &parse_ids(&params.ids)).await {
+        Ok(()) => {
+            admin_log(&admin, &client_ip(&headers), "调整章节顺序", params.articleid, "").await;
+            ApiResponse::success("success", Some(String::new()))
+        }
+        Err(e) => edit_fail(e),
+    }
+}
+
+/// 删除章节 同时删除txt
+pub(crate) async fn admin_chapter_delete(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<ChapterIdsFrom>
+) -> impl IntoResponse {
+    let ids = parse_ids(&params.ids);
+    match delete_chapters(params.articleid, &ids).await {
+        Ok(n) => {
+            admin_log(&admin, &client_ip(&headers), "删除章节", params.articleid, &join_ids(&ids)).await;
+            ApiResponse::success("success", Some(n))
+        }
+        Err(e) => edit_fail(e),
+    }
+}
+
+pub(crate) async fn admin_chapter_text(
+    Form(params): Form<ChapterIdFrom>
+) -> impl IntoResponse {
+    match get_chapter_text(params.articleid, params.id).await {
+        Ok(text) => ApiResponse::success("success", Some(text)),
+        Err(e) => edit_fail(e),
+    }
+}
+
+/// 保存正文 按原编码写回txt
+pub(crate) async fn admin_chapter_save(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<ChapterSaveFrom>
+) -> impl IntoResponse {
+    match save_chapter_text(params.articleid, params.id, &params.content).await {
+        Ok(()) => {
+            admin_log(&admin, &client_ip(&headers), "修改章节正文", params.articleid, &params.id.to_string()).await;
+            ApiResponse::success("success", Some(String::new()))
+        }
+        Err(e) => edit_fail(e),
+    }
+}
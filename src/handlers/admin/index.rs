@@ -1,38 +1,54 @@
-use std::env;
-use axum::extract::{Query, State};
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
 use axum::{Form, Json};
-use axum::response::IntoResponse;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::CookieJar;
+use cookie::Cookie;
 use serde::{Deserialize};
+use crate::handlers::search::html_resp;
 use crate::models::config::Config;
 use crate::routes;
+use crate::services::admin::{
+    break_glass_cookie, break_glass_enabled, current_admin, verify_break_glass, AdminRole, ADMIN_COOKIE,
+    ADMIN_COOKIE_TTL, BREAK_GLASS_NAME,
+};
 use crate::services::json::ApiResponse;
-use crate::services::user::timestamp_10;
+use crate::services::login_limit::{check_login, record_failure, record_success};
+use crate::services::session::SESSION_COOKIE;
 use crate::utils::conf::{get_config, set_config};
+use crate::utils::cookie::set_session_cookie;
 use crate::utils::file::get_folders;
+use crate::utils::request::{client_ip, is_https};
 use crate::utils::templates::render::{TeraRenderError, render_template};
 
 #[derive(Debug, Deserialize)]
-pub(crate) struct AdminFrom {
-    pub(crate) token: String,
-    pub(crate) time: Option<u64>
+pub(crate) struct AdminLoginFrom {
+    token: String,
+}
+
+/// 后台接口权限校验 用 middleware::from_fn_with_state(AdminRole::Xxx, admin_auth) 挂在路由上
+pub(crate) async fn admin_auth(
+    State(role): State<AdminRole>,
+    jar: CookieJar,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    match current_admin(&jar).await {
+        Some(admin) if admin.roles.contains(&role) => {
+            // 后续处理函数通过 Extension<AdminUser> 取操作者
+            req.extensions_mut().insert(admin);
+            next.run(req).await
+        }
+        Some(_) => ApiResponse::<()>::fail("没有权限", Some(vec!["当前账号没有这项后台权限".to_string()])).into_response(),
+        None => ApiResponse::<()>::fail("未登录", Some(vec!["登录已过期，请刷新后台页面".to_string()])).into_response(),
+    }
 }
 
 pub(crate) async fn admin_conf_edit(
-    Query(params): Query<AdminFrom>,
     Json(body): Json<Config>
 )-> impl IntoResponse {
-    let token = env::var("ADMIN_TOKEN").unwrap_or_else(|_| String::new());
-    #[cfg(not(debug_assertions))]   // 非调试模式下
-    {
-        if token.is_empty() || token == "admin_token" {
-            return ApiResponse::fail("token error", Some(vec!["默认密钥不可用".to_string()]));
-        }
-    }
-    let time = params.time.unwrap_or_else(|| 0);
-    let new_token = format!("{:x}",md5::compute(format!("{token}{time}",token=token,time=time)));
-    if new_token != params.token || time + 300 < timestamp_10() as u64 {
-        return ApiResponse::fail("token error", Some(vec!["密钥不正确或过期".to_string()]));
-    }
     let conf = match serde_json::to_string_pretty(&body) {
         Ok(s) => s,
         Err(e) => {
@@ -46,22 +62,8 @@ pub(crate) async fn admin_conf_edit(
     ApiResponse::success("success", Some(String::new()))
 }
 
-pub(crate) async fn admin_conf_get(
-    Form(params) : Form<AdminFrom>
-) -> impl IntoResponse{
-    let token = env::var("ADMIN_TOKEN").unwrap_or_else(|_| String::new());
-    #[cfg(not(debug_assertions))]   // 非调试模式下
-    {
-        if token.is_empty() || token == "admin_token" {
-            return ApiResponse::fail("token error", Some(vec!["默认密钥不可用".to_string()]));
-        }
-    }
-    let time = params.time.unwrap_or_else(|| 0);
-    let new_token = format!("{:x}",md5::compute(format!("{token}{time}",token=token,time=time)));
-    if new_token != params.token || time + 300 < timestamp_10() as u64 {
-        return ApiResponse::fail("token error", Some(vec!["密钥不正确或过期".to_string()]));
-    }
-    // 密钥验证成功把CONFIG序列化后返回
+pub(crate) async fn admin_conf_get() -> impl IntoResponse{
+    // 把CONFIG序列化后返回
     let conf = match serde_json::to_string_pretty(&*get_config()) {
         Ok(s) => s,
         Err(e) => {
@@ -71,33 +73,73 @@ pub(crate) async fn admin_conf_get(
     ApiResponse::success("success",Some(conf))
 }
 
+/// 后台首页 没有后台权限时显示登录提示 关闭了 ADMIN_TOKEN 时已登录的普通用户当作页面不存在
 pub(crate) async fn index(
     State(app_state): State<routes::app::AppState>,
-    Form(params) : Form<AdminFrom>
-) -> Result<impl IntoResponse, TeraRenderError> {
-    let mut ctx = tera::Context::new();
-    let token = env::var("ADMIN_TOKEN").unwrap_or_else(|_| String::new());
-    let time = timestamp_10();
-    #[cfg(not(debug_assertions))]   // 非调试模式下
-    {
-        if token.is_empty() || token == "admin_token" || params.token != token{
-            return Err(TeraRenderError::InvalidId);
-        }
-    }
-    #[cfg(debug_assertions)]    // 调试模式下 无需验证密钥
-    {
-        if token.is_empty() || params.token != token {
+    jar: CookieJar,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response, TeraRenderError> {
+    let Some(admin) = current_admin(&jar).await else {
+        if jar.get(SESSION_COOKIE).is_some() && !break_glass_enabled() {
             return Err(TeraRenderError::InvalidId);
         }
-    }
-    let new_token = format!("{:x}",md5::compute(format!("{token}{time}",token=token,time=time)));
+        return Ok(html_resp(login_page(uri.path(), "")));
+    };
+    let mut ctx = tera::Context::new();
     let folders = get_folders("templates");
     ctx.insert("themes", &folders);
-    ctx.insert("token", &new_token);
-    ctx.insert("time", &time);
+    ctx.insert("admin", &admin);
+    ctx.insert("sorts", &get_config().sort_arr);
     let html = render_template(app_state.tera.clone(), "admin.html", ctx).await?;
     Ok((
         [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
         html,
-    ))
+    ).into_response())
+}
+
+/// 未登录时的后台入口 ADMIN_TOKEN 只在应急时使用
+fn login_page(action: &str, msg: &str) -> String {
+    let form = if break_glass_enabled() {
+        format!(
+            r#"<form method="post" action="{}"><p>应急密钥</p><input type="password" name="token" required> <button type="submit">进入后台</button></form>"#,
+            html_escape::encode_double_quoted_attribute(action)
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1"><title>后台登录</title></head><body style="text-align:center;padding-top:80px"><p>{}</p><p>请使用管理员账号 <a href="/login">登录</a> 后再打开此页面</p>{}</body></html>"#,
+        msg, form
+    )
+}
+
+/// 用 ADMIN_TOKEN 进入后台 输错和登录一样计入失败次数
+pub(crate) async fn admin_login(
+    jar: CookieJar,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    Form(params): Form<AdminLoginFrom>,
+) -> Response {
+    let ip = client_ip(&headers);
+    if let Some(wait) = check_login(&ip, BREAK_GLASS_NAME).await {
+        return html_resp(login_page(uri.path(), &format!("尝试次数过多，请 {} 秒后再试", wait)));
+    }
+    let value = match break_glass_cookie() {
+        Some(value) if verify_break_glass(&params.token) => value,
+        _ => {
+            record_failure(&ip, BREAK_GLASS_NAME).await;
+            return html_resp(login_page(uri.path(), "密钥不正确"));
+        }
+    };
+    record_success(BREAK_GLASS_NAME).await;
+    let jar = set_session_cookie(jar, ADMIN_COOKIE, &value, ADMIN_COOKIE_TTL as usize, is_https(&headers));
+    (jar, Redirect::to(uri.path())).into_response()
+}
+
+/// 退出 ADMIN_TOKEN 登录 用户组登录的管理员在前台退出
+pub(crate) async fn admin_logout(jar: CookieJar) -> impl IntoResponse {
+    (
+        jar.remove(Cookie::build(ADMIN_COOKIE).path("/").build()),
+        ApiResponse::success("success", Some(String::new())),
+    )
 }
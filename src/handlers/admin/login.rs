@@ -0,0 +1,29 @@
+use axum::Form;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use crate::services::json::ApiResponse;
+use crate::services::login_limit::{clear_fails, list_fails};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LoginClearFrom {
+    /// 多个用英文逗号分隔 如 ip:1.2.3.4,user:admin
+    targets: String,
+}
+
+/// 登录失败和锁定记录
+pub(crate) async fn admin_login_list() -> impl IntoResponse {
+    ApiResponse::success("success", Some(list_fails().await))
+}
+
+/// 解除锁定
+pub(crate) async fn admin_login_clear(
+    Form(params): Form<LoginClearFrom>
+) -> impl IntoResponse {
+    let targets: Vec<String> = params.targets
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    clear_fails(&targets).await;
+    ApiResponse::success("success", Some(targets.len()))
+}
@@ -1 +1,6 @@
-pub(crate) mod index;
\ No newline at end of file
+pub(crate) mod index;
+pub(crate) mod report;
+pub(crate) mod search;
+pub(crate) mod login;
+pub(crate) mod novel;
+pub(crate) mod chapter;
@@ -0,0 +1,158 @@
+use axum::{Extension, Form};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use crate::handlers::admin::report::{join_ids, parse_ids};
+use crate::models::novel::NovelAdmin;
+use crate::services::admin::{admin_log, AdminUser};
+use crate::services::admin_novel::{get_novel, recount_novel, search_novels, set_display, update_novel, NovelEdit};
+use crate::services::json::ApiResponse;
+use crate::utils::conf::get_config;
+use crate::utils::request::client_ip;
+
+/// 后台每页显示的小说数
+const NOVEL_PER_PAGE: u64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NovelListFrom {
+    /// 小说ID、书名或作者
+    keyword: Option<String>,
+    page: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NovelIdFrom {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NovelEditFrom {
+    id: u64,
+    articlename: String,
+    author: String,
+    #[serde(default)]
+    intro: String,
+    #[serde(default)]
+    keywords: String,
+    sortid: u8,
+    #[serde(default)]
+    fullflag: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NovelDisplayFrom {
+    /// 多个ID用英文逗号分隔
+    ids: String,
+    /// 1 隐藏 0 显示
+    hidden: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct NovelList {
+    count: u64,
+    page: u64,
+    max_page: u64,
+    rows: Vec<NovelAdmin>,
+}
+
+fn db_fail<T>(action: &str, e: sea_orm::DbErr) -> ApiResponse<T> {
+    eprintln!("{action} error: {e:?}");
+    ApiResponse::fail("操作失败", Some(vec!["数据库操作失败".to_string()]))
+}
+
+pub(crate) async fn admin_novel_list(
+    Form(params): Form<NovelListFrom>
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    match search_novels(params.keyword.as_deref().unwrap_or(""), page, NOVEL_PER_PAGE).await {
+        Ok((count, rows)) => ApiResponse::success("success", Some(NovelList {
+            count,
+            page,
+            max_page: count.div_ceil(NOVEL_PER_PAGE).max(1),
+            rows,
+        })),
+        Err(e) => db_fail("search_novels", e),
+    }
+}
+
+pub(crate) async fn admin_novel_get(
+    Form(params): Form<NovelIdFrom>
+) -> impl IntoResponse {
+    match get_novel(params.id).await {
+        Ok(Some(row)) => ApiResponse::success("success", Some(row)),
+        Ok(None) => ApiResponse::fail("小说不存在", Some(vec!["小说不存在".to_string()])),
+        Err(e) => db_fail("get_novel", e),
+    }
+}
+
+/// 修改书名、作者、简介、关键字、分类和完本状态
+pub(crate) async fn admin_novel_edit(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<NovelEditFrom>
+) -> impl IntoResponse {
+    let articlename = params.articlename.trim();
+    let author = params.author.trim();
+    let mut errors = vec![];
+    if articlename.is_empty() || articlename.chars().count() > 50 {
+        errors.push("书名必须是1-50个字".to_string());
+    }
+    if author.is_empty() || author.chars().count() > 30 {
+        errors.push("作者必须是1-30个字".to_string());
+    }
+    if params.sortid == 0 || params.sortid as usize > get_config().sort_arr.len() {
+        errors.push("分类不存在".to_string());
+    }
+    if !errors.is_empty() {
+        return ApiResponse::fail("参数校验失败", Some(errors));
+    }
+    let edit = NovelEdit {
+        articlename: articlename.to_string(),
+        author: author.to_string(),
+        intro: params.intro,
+        keywords: params.keywords.trim().to_string(),
+        sortid: params.sortid,
+        fullflag: params.fullflag == 1,
+    };
+    match update_novel(params.id, &edit).await {
+        Ok(true) => {
+            admin_log(&admin, &client_ip(&headers), "修改小说", params.id, &format!("{} {}", edit.articlename, edit.author)).await;
+            ApiResponse::success("success", Some(String::new()))
+        }
+        Ok(false) => ApiResponse::fail("小说不存在", Some(vec!["小说不存在".to_string()])),
+        Err(e) => db_fail("update_novel", e),
+    }
+}
+
+/// 批量隐藏或显示
+pub(crate) async fn admin_novel_display(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<NovelDisplayFrom>
+) -> impl IntoResponse {
+    let ids = parse_ids(&params.ids);
+    match set_display(&ids, params.hidden == 1).await {
+        Ok(n) => {
+            let action = if params.hidden == 1 { "隐藏小说" } else { "显示小说" };
+            admin_log(&admin, &client_ip(&headers), action, 0, &join_ids(&ids)).await;
+            ApiResponse::success("success", Some(n))
+        }
+        Err(e) => db_fail("set_display", e),
+    }
+}
+
+/// 按章节表重新统计最新章节、章节数和字数
+pub(crate) async fn admin_novel_recount(
+    Extension(admin): Extension<AdminUser>,
+    headers: HeaderMap,
+    Form(params): Form<NovelIdFrom>
+) -> impl IntoResponse {
+    match recount_novel(params.id).await {
+        Ok(Some(stats)) => {
+            admin_log(&admin, &client_ip(&headers), "重新统计", params.id, "").await;
+            ApiResponse::success("success", Some(stats))
+        }
+        Ok(None) => ApiResponse::fail("小说不存在", Some(vec!["小说不存在".to_string()])),
+        Err(e) => db_fail("recount_novel", e),
+    }
+}
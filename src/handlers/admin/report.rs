@@ -0,0 +1,86 @@
+use axum::Form;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use crate::models::report::{ChapterReport, REPORT_PENDING};
+use crate::services::json::ApiResponse;
+use crate::services::report::{dismiss_reports, get_report_list, resolve_reports};
+
+/// 后台每页显示的报错数
+const REPORT_PER_PAGE: u64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReportListFrom {
+    status: Option<u8>,
+    page: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReportIdsFrom {
+    /// 多个ID用英文逗号分隔
+    ids: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ReportList {
+    count: u64,
+    page: u64,
+    max_page: u64,
+    rows: Vec<ChapterReport>,
+}
+
+pub(crate) async fn admin_report_list(
+    Form(params): Form<ReportListFrom>
+) -> impl IntoResponse {
+    let status = params.status.unwrap_or(REPORT_PENDING);
+    let page = params.page.unwrap_or(1).max(1);
+    match get_report_list(status, page, REPORT_PER_PAGE).await {
+        Ok((count, rows)) => ApiResponse::success("success", Some(ReportList {
+            count,
+            page,
+            max_page: count.div_ceil(REPORT_PER_PAGE).max(1),
+            rows,
+        })),
+        Err(e) => {
+            eprintln!("get_report_list error: {e:?}");
+            ApiResponse::fail("查询失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+/// 标记已处理 会清除对应小说的缓存
+pub(crate) async fn admin_report_resolve(
+    Form(params): Form<ReportIdsFrom>
+) -> impl IntoResponse {
+    match resolve_reports(&parse_ids(&params.ids)).await {
+        Ok(n) => ApiResponse::success("success", Some(n)),
+        Err(e) => {
+            eprintln!("resolve_reports error: {e:?}");
+            ApiResponse::fail("处理失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+/// 批量忽略
+pub(crate) async fn admin_report_dismiss(
+    Form(params): Form<ReportIdsFrom>
+) -> impl IntoResponse {
+    match dismiss_reports(&parse_ids(&params.ids)).await {
+        Ok(n) => ApiResponse::success("success", Some(n)),
+        Err(e) => {
+            eprintln!("dismiss_reports error: {e:?}");
+            ApiResponse::fail("忽略失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+pub(crate) fn parse_ids(ids: &str) -> Vec<u64> {
+    ids.split(',')
+        .filter_map(|s| s.trim().parse::<u64>().ok())
+        .filter(|id| *id > 0)
+        .collect()
+}
+
+/// parse_ids 的反向 用于记录日志
+pub(crate) fn join_ids(ids: &[u64]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",")
+}
@@ -0,0 +1,42 @@
+use axum::Form;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use crate::services::json::ApiResponse;
+use crate::services::search::{get_keyword_list, SearchKeyword};
+
+/// 后台每页显示的搜索词数
+const KEYWORD_PER_PAGE: u64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct KeywordListFrom {
+    /// hot 热门搜索 miss 无结果的搜索
+    kind: Option<String>,
+    page: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct KeywordList {
+    count: u64,
+    page: u64,
+    max_page: u64,
+    rows: Vec<SearchKeyword>,
+}
+
+pub(crate) async fn admin_search_list(
+    Form(params): Form<KeywordListFrom>
+) -> impl IntoResponse {
+    let hit = params.kind.as_deref() != Some("miss");
+    let page = params.page.unwrap_or(1).max(1);
+    match get_keyword_list(hit, page, KEYWORD_PER_PAGE).await {
+        Ok((count, rows)) => ApiResponse::success("success", Some(KeywordList {
+            count,
+            page,
+            max_page: count.div_ceil(KEYWORD_PER_PAGE).max(1),
+            rows,
+        })),
+        Err(e) => {
+            eprintln!("get_keyword_list error: {e:?}");
+            ApiResponse::fail("查询失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
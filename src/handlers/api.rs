@@ -0,0 +1,173 @@
+use axum::extract::{Path, Query};
+use axum::extract::rejection::QueryRejection;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::handlers::rank::RANK_ALL;
+use crate::services;
+use crate::services::json::ApiResponse;
+use crate::services::novel::extract_id;
+use crate::utils::conf::get_config;
+use crate::utils::request::request_host;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiPageQuery {
+    page: Option<u64>,
+    sort: Option<String>,
+}
+
+/// 小说信息 id 为混淆后的ID
+pub(crate) async fn api_novel(Path(id): Path<String>, headers: HeaderMap) -> ApiResponse<Value> {
+    let Some(id) = extract_id(&id) else {
+        return ApiResponse::fail("ID错误", None);
+    };
+    let source_id = get_config().source_id(id);
+    let Some(row) = services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id)
+        .await
+        .into_iter()
+        .next()
+    else {
+        return ApiResponse::fail("小说不存在", None);
+    };
+    services::visit::record_visit(&headers, source_id).await;
+    ApiResponse::success("ok", Some(hide_source_id(&row)))
+}
+
+/// 章节列表
+pub(crate) async fn api_chapters(Path(id): Path<String>, headers: HeaderMap) -> ApiResponse<Value> {
+    let Some(id) = extract_id(&id) else {
+        return ApiResponse::fail("ID错误", None);
+    };
+    let source_id = get_config().source_id(id);
+    // 隐藏的小说查不到信息 章节列表和正文也不能返回
+    if services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id).await.is_empty() {
+        return ApiResponse::fail("小说不存在", None);
+    }
+    let chapter_rows = services::novel::get_chapter_rows(request_host(&headers), get_config().cache.info, source_id).await;
+    if chapter_rows.is_empty() {
+        return ApiResponse::fail("小说不存在或暂无章节", None);
+    }
+    ApiResponse::success("ok", Some(hide_source_id(&chapter_rows)))
+}
+
+/// 章节正文 不分页 每段一行 prev_id/next_id 为 0 表示没有上一章/下一章
+pub(crate) async fn api_chapter(Path((id, cid)): Path<(String, String)>, headers: HeaderMap) -> ApiResponse<Value> {
+    let (Some(id), Some(cid)) = (extract_id(&id), extract_id(&cid)) else {
+        return ApiResponse::fail("ID错误", None);
+    };
+    let source_id = get_config().source_id(id);
+    // 隐藏的小说查不到信息 章节列表和正文也不能返回
+    if services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id).await.is_empty() {
+        return ApiResponse::fail("小说不存在", None);
+    }
+    let chapter_rows = services::novel::get_chapter_rows(request_host(&headers), get_config().cache.info, source_id).await;
+    let Some(index) = chapter_rows.iter().position(|c| c.chapterid == cid) else {
+        return ApiResponse::fail("章节不存在", None);
+    };
+    let chapter = &chapter_rows[index];
+    services::visit::record_visit(&headers, source_id).await;
+    let content = services::novel::get_chapter_text(source_id, chapter.source_id).await;
+    let prev_id = if index > 0 { chapter_rows[index - 1].chapterid } else { 0 };
+    let next_id = chapter_rows.get(index + 1).map(|c| c.chapterid).unwrap_or(0);
+    ApiResponse::success("ok", Some(json!({
+        "chapter": hide_source_id(chapter),
+        "content": content,
+        "prev_id": prev_id,
+        "next_id": next_id,
+    })))
+}
+
+/// 分类列表 id 与分类页伪静态中的 {id} 一致
+pub(crate) async fn api_sorts() -> ApiResponse<Value> {
+    let sorts: Vec<Value> = get_config().sort_arr.iter().enumerate().map(|(i, s)| json!({
+        "id": i + 1,
+        "code": s.code,
+        "caption": s.caption,
+        "url": get_config().sort_url(&s.code, i + 1, 1),
+    })).collect();
+    ApiResponse::success("ok", Some(Value::Array(sorts)))
+}
+
+/// 分类下的小说 按更新时间排序
+pub(crate) async fn api_sort(
+    Path(code): Path<String>,
+    params: Result<Query<ApiPageQuery>, QueryRejection>,
+    headers: HeaderMap,
+) -> ApiResponse<Value> {
+    let Ok(Query(params)) = params else {
+        return ApiResponse::fail("参数错误", None);
+    };
+    let Some(sort_index) = get_config().sort_arr.iter().position(|s| s.code == code) else {
+        return ApiResponse::fail("分类不存在", None);
+    };
+    let page = params.page.unwrap_or(1);
+    let (max_page, rows) = services::novel::get_sort_page(request_host(&headers), sort_index, page).await;
+    if page == 0 || page > max_page {
+        return ApiResponse::fail("页码超出范围", None);
+    }
+    ApiResponse::success("ok", Some(page_json(page, max_page, &rows)))
+}
+
+/// 排行榜列表
+pub(crate) async fn api_ranks() -> ApiResponse<Value> {
+    let ranks: Vec<Value> = get_config().rank_arr.iter().map(|r| json!({
+        "code": r.code,
+        "caption": r.caption,
+        "url": get_config().rank_url(&r.code),
+    })).collect();
+    ApiResponse::success("ok", Some(Value::Array(ranks)))
+}
+
+/// 排行榜 sort 为分类拼音 不传或为 all 时不按分类筛选
+pub(crate) async fn api_rank(
+    Path(code): Path<String>,
+    params: Result<Query<ApiPageQuery>, QueryRejection>,
+    headers: HeaderMap,
+) -> ApiResponse<Value> {
+    let Ok(Query(params)) = params else {
+        return ApiResponse::fail("参数错误", None);
+    };
+    let Some(rank) = get_config().get_rank(&code).cloned() else {
+        return ApiResponse::fail("排行榜不存在", None);
+    };
+    let sort_index = match params.sort.as_deref() {
+        None | Some(RANK_ALL) => None,
+        Some(sort) => match get_config().sort_arr.iter().position(|s| s.code == sort) {
+            Some(i) => Some(i),
+            None => return ApiResponse::fail("分类不存在", None),
+        },
+    };
+    let page = params.page.unwrap_or(1);
+    let (max_page, rows) = services::novel::get_rank_page(request_host(&headers), &rank, sort_index, page).await;
+    if page == 0 || page > max_page {
+        return ApiResponse::fail("页码超出范围", None);
+    }
+    ApiResponse::success("ok", Some(page_json(page, max_page, &rows)))
+}
+
+fn page_json<T: Serialize>(page: u64, max_page: u64, rows: &T) -> Value {
+    json!({
+        "page": page,
+        "max_page": max_page,
+        "rows": hide_source_id(rows),
+    })
+}
+
+/// 去掉模型里的 source_id 接口与页面一样只暴露混淆后的ID
+fn hide_source_id<T: Serialize>(data: &T) -> Value {
+    let mut value = serde_json::to_value(data).unwrap_or(Value::Null);
+    match &mut value {
+        Value::Object(map) => {
+            map.remove("source_id");
+        }
+        Value::Array(rows) => {
+            for row in rows.iter_mut() {
+                if let Value::Object(map) = row {
+                    map.remove("source_id");
+                }
+            }
+        }
+        _ => {}
+    }
+    value
+}
@@ -0,0 +1,39 @@
+use axum::extract::Query;
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use crate::services::captcha::{create_captcha, new_id, CAPTCHA_COOKIE};
+use crate::utils::cookie::set_cookie_value;
+use crate::utils::request::is_https;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CaptchaQuery {
+    #[serde(default)]
+    id: String,
+}
+
+/// 验证码图片 没有 ss_captcha cookie 时先发一个
+pub(crate) async fn get_captcha(
+    mut jar: CookieJar,
+    headers: HeaderMap,
+    Query(params): Query<CaptchaQuery>,
+) -> Response {
+    let client = match jar.get(CAPTCHA_COOKIE) {
+        Some(c) => c.value().to_string(),
+        None => {
+            let client = new_id();
+            jar = set_cookie_value(jar, CAPTCHA_COOKIE, &client, 86400 * 30, true, is_https(&headers));
+            client
+        }
+    };
+    match create_captcha(&client, &params.id).await {
+        Some(png) => (
+            jar,
+            [(CONTENT_TYPE, "image/png"), (CACHE_CONTROL, "no-store")],
+            png,
+        ).into_response(),
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
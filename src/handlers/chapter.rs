@@ -5,16 +5,11 @@ use axum::extract::{OriginalUri, Path, State};
 use axum::http::header::HOST;
 use axum::http::{HeaderMap};
 use axum::response::IntoResponse;
-use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::Deserialize;
 use unicode_segmentation::UnicodeSegmentation;
 use crate::utils::conf::get_config;
 use crate::utils::file::file_exists;
 
-static BR_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"(?i)<br\s+[^>]*?/?>").expect("正则编译失败"));
-
 #[derive(Deserialize)]
 #[allow(dead_code)]
 pub(crate) struct ChapterPath {
@@ -90,6 +85,7 @@ pub(crate) async fn get_chapter(
             .into_iter()
             .next()
             .ok_or(TeraRenderError::InvalidId)?;
+    services::visit::record_visit(&headers, source_id).await;
     let chapter_rows =
         services::novel::get_chapter_rows(url, get_config().cache.info, source_id)
             .await;
@@ -118,18 +114,10 @@ pub(crate) async fn get_chapter(
             .read_url(row.articleid, chapter_rows[chapter_index - 1].chapterid, 1)
     };
     // 拼接小说章节内容
-    let txt_url = format!(
-        "{}/{}/{}/{}.txt",
-        get_config().txt_url,
-        source_id / 1000,
-        source_id,
-        source_chapter_id
-    );
-    let mut chapter_content = services::novel::read_file(&txt_url).await;
+    let mut chapter_content = services::novel::get_chapter_content(source_id, source_chapter_id).await;
     if chapter_content.is_empty() {
         chapter_content = "章节正在手打中，请稍后重新访问！".to_string();
     } else {
-        chapter_content = BR_REGEX.replace_all(&chapter_content, "\n").to_string();
         match get_config().read_page_split_mode {
             1 => {  // 按行数分
                 if get_config().read_page_split_lines
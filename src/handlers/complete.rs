@@ -0,0 +1,124 @@
+use axum::extract::{OriginalUri, Path, State};
+use axum::http::{HeaderMap};
+use axum::http::header::HOST;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use crate::{routes, services};
+use crate::handlers::index_list::IndexListPageUrl;
+use crate::services::novel::{generate_pagination_numbers, extract_str, extract_id};
+use crate::utils::conf::get_config;
+use crate::utils::file::file_exists;
+use crate::utils::templates::render;
+use crate::utils::templates::render::TeraRenderError;
+
+/// {sort} 取这个值时表示不按分类筛选
+pub(crate) const COMPLETE_ALL: &str = "all";
+
+#[derive(Deserialize)]
+pub(crate) struct CompletePath {
+    sort: Option<String>,
+    page: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CompleteSortUrl {
+    title: String,
+    url: String,
+    select: bool,
+}
+
+pub(crate) async fn get_complete(
+    Path(p): Path<CompletePath>,
+    State(app_state): State<routes::app::AppState>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+) -> Result<impl IntoResponse, TeraRenderError> {
+    if !file_exists(format!("templates/{}/complete.html", get_config().theme_dir)) {
+        println!("No such file or directory");
+        return Err(TeraRenderError::InvalidId);
+    }
+    let mut page = 1;
+    if let Some(sid) = p.page {
+        page = extract_id(&sid).ok_or(TeraRenderError::InvalidId)?;
+    }
+    if page == 0 {
+        return Err(TeraRenderError::InvalidId);
+    }
+    // 未配置{sort}或传入all时 显示全部分类
+    let code = match p.sort {
+        Some(s) => extract_str(&s).ok_or(TeraRenderError::InvalidId)?.to_string(),
+        None => COMPLETE_ALL.to_string(),
+    };
+    let sort_id = if code == COMPLETE_ALL {
+        None
+    } else {
+        Some(get_config().sort_arr.iter()
+            .position(|s| s.code == code)
+            .ok_or(TeraRenderError::InvalidId)?)
+    };
+    let url = headers
+        .get(HOST)
+        .and_then(|v| v.to_str().ok()) // 安全转换为字符串
+        .unwrap_or("unknown.host");
+    let (max_page, rows) = services::novel::get_complete_page(url, sort_id, page).await;
+    if page > max_page {
+        return Err(TeraRenderError::InvalidId);
+    }
+    let prev_url = if page > 1 {
+        get_config().complete_url(&code, (page - 1) as usize)
+    } else {
+        String::new()
+    };
+    let next_url = if page < max_page {
+        get_config().complete_url(&code, (page + 1) as usize)
+    } else {
+        String::new()
+    };
+    let mut jump_pages: Vec<IndexListPageUrl> = Vec::new();
+    let j = generate_pagination_numbers(page as usize, max_page);
+    for p in j {
+        jump_pages.push(IndexListPageUrl {
+            page: p as u64,
+            url: get_config().complete_url(&code, p),
+            select: p == page as usize,
+        });
+    }
+    // 分类筛选 只有伪静态里有{sort}时才生成
+    let mut sort_nav: Vec<CompleteSortUrl> = Vec::new();
+    if get_config().rewrite.complete_url.contains("{sort}") {
+        sort_nav.push(CompleteSortUrl {
+            title: "全部".to_string(),
+            url: get_config().complete_url(COMPLETE_ALL, 1),
+            select: sort_id.is_none(),
+        });
+        for (i, s) in get_config().sort_arr.iter().enumerate() {
+            sort_nav.push(CompleteSortUrl {
+                title: s.caption.clone(),
+                url: get_config().complete_url(&s.code, 1),
+                select: sort_id == Some(i),
+            });
+        }
+    }
+    let sort = sort_id.map(|i| get_config().sort_arr[i].clone());
+    let title = match &sort {
+        Some(s) => format!("{}完本", s.caption),
+        None => "完本小说".to_string(),
+    };
+    let mut ctx = tera::Context::new();
+    services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    ctx.insert("title", &title);
+    ctx.insert("sort", &sort);
+    ctx.insert("sort_nav", &sort_nav);
+    ctx.insert("rows", &rows);
+    ctx.insert("prev_url", &prev_url);
+    ctx.insert("next_url", &next_url);
+    ctx.insert("page", &page);
+    ctx.insert("max_page", &max_page);
+    ctx.insert("jump_pages", &jump_pages);
+    let template_path = format!("{}/complete.html", get_config().theme_dir);
+    let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    ))
+}
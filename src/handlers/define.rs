@@ -3,3 +3,5 @@ pub(crate) static NOVEL_FIELD_2: &str = "articlecode,words,articleid,articlename
 pub(crate) static NOVEL_WHERE: &str = "display <> 1 AND size >= 0 ";
 pub(crate) static NOVEL_WHERE_2: &str = "display <> 1 AND words >= 0 ";
 pub(crate) static NOVEL_CHAPTER_FILED: &str = "articleid,chapterid,chaptername,lastupdate,chaptertype,chapterorder";
+/// 排行榜允许使用的排序字段 配置中的排行榜只能从这里选
+pub(crate) static RANK_COLUMNS: &[&str] = &["allvisit", "monthvisit", "weekvisit", "dayvisit", "allvote", "monthvote", "weekvote", "dayvote", "goodnum", "lastupdate"];
@@ -0,0 +1,130 @@
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
+use crate::services::epub;
+use crate::services::novel::{extract_id, get_chapter_content, get_chapter_rows, get_novel_info};
+use crate::utils::conf::{get_config, multi_replace};
+use crate::utils::request::request_host;
+use crate::utils::templates::render::TeraRenderError;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct DownPath {
+    sid: Option<u64>,
+    id: String,
+}
+
+/// 整本TXT下载 按章节逐个读取后推送 不在内存中拼接整本书
+pub(crate) async fn get_down(
+    Path(p): Path<DownPath>,
+    headers: HeaderMap,
+) -> Result<Response, TeraRenderError> {
+    if !get_config().enable_down {
+        return Err(TeraRenderError::InvalidId);
+    }
+    let id = extract_id(&p.id).ok_or(TeraRenderError::InvalidId)?;
+    let source_id = get_config().source_id(id);
+    let url = request_host(&headers);
+    let row = get_novel_info(url, get_config().cache.info, source_id)
+        .await
+        .into_iter()
+        .next()
+        .ok_or(TeraRenderError::InvalidId)?;
+    let chapter_rows = get_chapter_rows(url, get_config().cache.info, source_id).await;
+    if chapter_rows.is_empty() {
+        return Err(TeraRenderError::InvalidId);
+    }
+    let head = format!(
+        "《{}》\n作者：{}\n分类：{}\n状态：{}\n\n简介：\n{}\n\n{}\n",
+        row.articlename,
+        row.author,
+        row.sortname,
+        row.isfull,
+        row.intro.trim(),
+        get_config().site_url
+    );
+    let body = stream::once(async move { Ok::<String, std::io::Error>(multi_replace(&head)) })
+        .chain(stream::iter(chapter_rows).then(move |chapter| async move {
+            // chaptertype = 1 为分卷 没有对应的TXT文件
+            if chapter.chaptertype == 1 {
+                return Ok(format!("\n\n【{}】\n", chapter.chaptername));
+            }
+            let content = get_chapter_content(source_id, chapter.source_id).await;
+            let mut out = String::with_capacity(content.len() + chapter.chaptername.len() + 8);
+            out.push_str("\n\n");
+            out.push_str(&chapter.chaptername);
+            out.push_str("\n\n");
+            for line in content.lines() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                out.push_str("　　");
+                out.push_str(line);
+                out.push('\n');
+            }
+            Ok(multi_replace(&out))
+        }));
+    let file_name = format!("{}.txt", row.articlename);
+    let disposition = format!(
+        "attachment; filename=\"{}.txt\"; filename*=UTF-8''{}",
+        id,
+        urlencoding::encode(&file_name)
+    );
+    Ok((
+        [
+            (CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(body),
+    ).into_response())
+}
+
+/// EPUB 下载 文件缓存在磁盘上 以最新章节ID判断是否需要重新生成
+pub(crate) async fn get_epub(
+    Path(p): Path<DownPath>,
+    headers: HeaderMap,
+) -> Result<Response, TeraRenderError> {
+    if !get_config().enable_down {
+        return Err(TeraRenderError::InvalidId);
+    }
+    let id = extract_id(&p.id).ok_or(TeraRenderError::InvalidId)?;
+    let source_id = get_config().source_id(id);
+    let url = request_host(&headers);
+    let row = get_novel_info(url, get_config().cache.info, source_id)
+        .await
+        .into_iter()
+        .next()
+        .ok_or(TeraRenderError::InvalidId)?;
+    let path = epub::get_epub(&row, url).await.map_err(|e| {
+        eprintln!("epub build error article_id={}: {:?}", source_id, e);
+        TeraRenderError::InvalidId
+    })?;
+    let file = tokio::fs::File::open(&path).await.map_err(|e| {
+        eprintln!("epub read error path={:?}: {:?}", path, e);
+        TeraRenderError::InvalidId
+    })?;
+    let len = file.metadata().await.map(|m| m.len()).ok();
+    let file_name = format!("{}.epub", row.articlename);
+    let disposition = format!(
+        "attachment; filename=\"{}.epub\"; filename*=UTF-8''{}",
+        id,
+        urlencoding::encode(&file_name)
+    );
+    let mut response = (
+        [
+            (CONTENT_TYPE, "application/epub+zip".to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ).into_response();
+    if let Some(len) = len {
+        response.headers_mut().insert(CONTENT_LENGTH, len.into());
+    }
+    Ok(response)
+}
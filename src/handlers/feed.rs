@@ -0,0 +1,38 @@
+use axum::extract::Path;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use crate::services::feed::{get_novel_feed, get_site_feed};
+use crate::services::novel::extract_id;
+use crate::utils::conf::get_config;
+use crate::utils::request::{request_host, site_base_url};
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct FeedPath {
+    sid: Option<u64>,
+    id: String,
+}
+
+/// 全站最近更新 Atom
+pub(crate) async fn get_feed(headers: HeaderMap) -> Response {
+    let xml = get_site_feed(request_host(&headers), &site_base_url(&headers)).await;
+    atom_resp(xml)
+}
+
+/// 单本小说最新章节 Atom
+pub(crate) async fn get_novel_feed_xml(Path(p): Path<FeedPath>, headers: HeaderMap) -> Response {
+    let Some(id) = extract_id(&p.id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let source_id = get_config().source_id(id);
+    match get_novel_feed(request_host(&headers), &site_base_url(&headers), source_id).await {
+        Some(xml) => atom_resp(xml),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn atom_resp(s: String) -> Response {
+    ([(CONTENT_TYPE, "application/atom+xml; charset=utf-8")], s).into_response()
+}
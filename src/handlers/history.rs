@@ -1,7 +1,9 @@
 use axum::extract::{OriginalUri, State};
 use axum::http::{HeaderMap};
 use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
 use crate::{routes, services};
+use crate::handlers::user::history::history_items;
 use crate::utils::conf::get_config;
 use crate::utils::file::file_exists;
 use crate::utils::templates::render;
@@ -11,6 +13,7 @@ pub(crate) async fn get_history(
     State(app_state): State<routes::app::AppState>,
     headers: HeaderMap,
     OriginalUri(uri): OriginalUri,
+    jar: CookieJar,
 ) -> Result<impl IntoResponse, TeraRenderError> {
     if !file_exists(format!("templates/{}/history.html", get_config().theme_dir)) {
         println!("No such file or directory");
@@ -18,9 +21,20 @@ pub(crate) async fn get_history(
     }
     let mut ctx = tera::Context::new();
     services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    // 登录用户由服务端输出记录 未登录仍由 history.js 读取本地记录
+    let (jar, session) = services::session::current_session(jar, &headers).await;
+    if let Some(session) = session {
+        let list = services::history::get_history_list(session.userid).await.unwrap_or_else(|e| {
+            eprintln!("get_history_list error: {e:?}");
+            vec![]
+        });
+        ctx.insert("is_login", &true);
+        ctx.insert("history_list", &history_items(&list));
+    }
     let template_path = format!("{}/history.html", get_config().theme_dir);
     let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
     Ok((
+        jar,
         [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
         html,
     ))
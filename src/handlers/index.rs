@@ -25,7 +25,7 @@ pub async fn get_index(
         None,
     ).await;
     let popular = get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article ORDER BY monthvisit DESC LIMIT 25",filed=get_config().get_field(),table=get_config().prefix),
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} ORDER BY monthvisit DESC LIMIT 25",filed=get_config().get_field(),table=get_config().prefix,where=get_config().get_where()),
         url,
         get_config().cache.home as u64,
         None,
@@ -46,13 +46,13 @@ pub async fn get_index(
         sortarr.insert(i, rows);
     }
     let lastupdate = get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article ORDER BY lastupdate DESC LIMIT 30",filed=get_config().get_field(),table=get_config().prefix),
+        services::novel::lastupdate_sql(),
         url,
         get_config().cache.home as u64,
         None,
     ).await;
     let postdate = get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article ORDER BY postdate DESC LIMIT 30",filed=get_config().get_field(),table=get_config().prefix),
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} ORDER BY postdate DESC LIMIT 30",filed=get_config().get_field(),table=get_config().prefix,where=get_config().get_where()),
         url,
         get_config().cache.home as u64,
         None,
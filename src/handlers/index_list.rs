@@ -2,6 +2,7 @@ use crate::models::novel::NovelChapter;
 use crate::routes::app::AppState;
 use crate::services::lang_tail::{gen_lang_tail, get_lang_tail, get_lang_tail_array};
 use crate::services::novel::{extract_id, get_chapter_rows, get_novel_info, process_tera_tag};
+use crate::services::visit::record_visit;
 use crate::utils::conf::get_config;
 use crate::utils::file::file_exists;
 use crate::utils::templates::render;
@@ -64,6 +65,7 @@ pub(crate) async fn get_index_list(
         .into_iter()
         .next()
         .ok_or(TeraRenderError::InvalidId)?;
+    record_visit(&headers, source_id).await;
     let chapter_rows = get_chapter_rows(url, get_config().cache.info, source_id).await;
     let last_12 = &chapter_rows[chapter_rows.len().saturating_sub(12)..];
     let last_chapter: NovelChapter = if chapter_rows.is_empty() {
@@ -190,6 +192,7 @@ pub(crate) async fn get_lang_index(
         .into_iter()
         .next()
         .ok_or(TeraRenderError::InvalidId)?;
+    record_visit(&headers, lang_row.sourceid).await;
     let chapter_rows = get_chapter_rows(url, get_config().cache.info, lang_row.sourceid).await;
     let last_12 = &chapter_rows[chapter_rows.len().saturating_sub(12)..];
     let last_chapter: NovelChapter = if chapter_rows.is_empty() {
@@ -61,6 +61,7 @@ pub(crate) async fn get_info(
         .and_then(|v| v.to_str().ok())
         .unwrap_or("unknown.host");
     let row = get_novel_info(url, get_config().cache.info, source_id).await.into_iter().next().ok_or(TeraRenderError::InvalidId)?;
+    services::visit::record_visit(&headers, source_id).await;
     let chapter_rows = get_chapter_rows(url, get_config().cache.info, source_id).await;
     let last_12 = &chapter_rows[chapter_rows.len().saturating_sub(12)..];
     let last_chapter:NovelChapter = if chapter_rows.is_empty() {
@@ -112,6 +113,7 @@ pub(crate) async fn get_lang(
         .unwrap_or("unknown.host");
     let lang_row = services::lang_tail::get_lang_tail(source_lang_id,url).await.into_iter().next().ok_or(TeraRenderError::InvalidId)?;
     let mut row = get_novel_info(url, get_config().cache.info, lang_row.sourceid).await.into_iter().next().ok_or(TeraRenderError::InvalidId)?;
+    services::visit::record_visit(&headers, lang_row.sourceid).await;
     let chapter_rows = get_chapter_rows(url, get_config().cache.info, lang_row.sourceid).await;
     let last_12 = &chapter_rows[chapter_rows.len().saturating_sub(12)..];
     let last_chapter:NovelChapter = if chapter_rows.is_empty() {
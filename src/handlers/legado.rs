@@ -0,0 +1,205 @@
+use axum::extract::{Path, Query};
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::models::novel::Novel;
+use crate::services;
+use crate::services::json::ApiResponse;
+use crate::utils::conf::get_config;
+use crate::utils::request::{client_ip, request_host, site_base_url};
+
+/// 阅读(Legado) 书源接口 路径固定 不随伪静态变化
+const LEGADO_PREFIX: &str = "/legado";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LegadoSearchQuery {
+    key: String,
+    page: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LegadoBook {
+    name: String,
+    author: String,
+    intro: String,
+    kind: String,
+    last_chapter: String,
+    cover_url: String,
+    book_url: String,
+    toc_url: String,
+    web_url: String,
+    word_count: String,
+    update_time: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LegadoChapter {
+    title: String,
+    url: String,
+    is_volume: bool,
+    update_time: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct LegadoContent {
+    title: String,
+    content: String,
+}
+
+/// 书源 JSON 按当前站点地址生成 在阅读中通过网络导入
+pub(crate) async fn get_legado_source(headers: HeaderMap) -> Json<Value> {
+    let base = site_base_url(&headers);
+    Json(json!([{
+        "bookSourceName": get_config().site_name,
+        "bookSourceUrl": base,
+        "bookSourceGroup": get_config().site_name,
+        "bookSourceType": 0,
+        "bookSourceComment": format!("由 {} 自动生成 接口地址 {}{}", get_config().site_name, base, LEGADO_PREFIX),
+        "enabled": true,
+        "enabledExplore": false,
+        "enabledCookieJar": false,
+        "customOrder": 0,
+        "weight": 0,
+        "lastUpdateTime": Local::now().timestamp_millis(),
+        "searchUrl": format!("{}/search?key={{{{key}}}}&page={{{{page}}}}", LEGADO_PREFIX),
+        "ruleSearch": {
+            "bookList": "$.data[*]",
+            "name": "$.name",
+            "author": "$.author",
+            "intro": "$.intro",
+            "kind": "$.kind",
+            "lastChapter": "$.lastChapter",
+            "coverUrl": "$.coverUrl",
+            "bookUrl": "$.bookUrl",
+            "wordCount": "$.wordCount",
+        },
+        "ruleBookInfo": {
+            "init": "$.data",
+            "name": "$.name",
+            "author": "$.author",
+            "intro": "$.intro",
+            "kind": "$.kind",
+            "lastChapter": "$.lastChapter",
+            "coverUrl": "$.coverUrl",
+            "tocUrl": "$.tocUrl",
+            "wordCount": "$.wordCount",
+        },
+        "ruleToc": {
+            "chapterList": "$.data[*]",
+            "chapterName": "$.title",
+            "chapterUrl": "$.url",
+            "isVolume": "$.isVolume",
+            "updateTime": "$.updateTime",
+        },
+        "ruleContent": {
+            "content": "$.data.content",
+        },
+    }]))
+}
+
+/// 搜索 与搜索页使用同一条查询 按IP限制搜索间隔和次数
+pub(crate) async fn legado_search(
+    Query(params): Query<LegadoSearchQuery>,
+    headers: HeaderMap,
+) -> ApiResponse<Vec<LegadoBook>> {
+    let keyword = params.key.trim();
+    let page = params.page.unwrap_or(1).max(1);
+    if let Err(e) = services::search::check_search(&client_ip(&headers), keyword, page, None, None).await {
+        return ApiResponse::fail(&e.to_string(), None);
+    }
+    let limit = get_config().search.limit.clamp(1, 100) as u64;
+    let (_, rows) = services::search::search_novels(request_host(&headers), keyword, page, limit).await;
+    if page == 1 {
+        services::search::record_keyword(keyword, !rows.is_empty()).await;
+    }
+    let base = site_base_url(&headers);
+    ApiResponse::success("ok", Some(rows.iter().map(|row| legado_book(&base, row)).collect()))
+}
+
+/// 书籍详情 id 为混淆后的ID
+pub(crate) async fn legado_book_info(Path(id): Path<u64>, headers: HeaderMap) -> ApiResponse<LegadoBook> {
+    let source_id = get_config().source_id(id);
+    let Some(row) = services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id)
+        .await
+        .into_iter()
+        .next()
+    else {
+        return ApiResponse::fail("小说不存在", None);
+    };
+    ApiResponse::success("ok", Some(legado_book(&site_base_url(&headers), &row)))
+}
+
+/// 目录
+pub(crate) async fn legado_toc(Path(id): Path<u64>, headers: HeaderMap) -> ApiResponse<Vec<LegadoChapter>> {
+    let source_id = get_config().source_id(id);
+    // 隐藏的小说查不到信息 目录和正文也不能返回
+    if services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id).await.is_empty() {
+        return ApiResponse::fail("小说不存在", None);
+    }
+    let chapter_rows = services::novel::get_chapter_rows(request_host(&headers), get_config().cache.info, source_id).await;
+    if chapter_rows.is_empty() {
+        return ApiResponse::fail("小说不存在或暂无章节", None);
+    }
+    let base = site_base_url(&headers);
+    let chapters = chapter_rows.iter().map(|chapter| LegadoChapter {
+        title: chapter.chaptername.clone(),
+        url: format!("{}{}/chapter/{}/{}", base, LEGADO_PREFIX, id, chapter.chapterid),
+        // chaptertype = 1 为分卷
+        is_volume: chapter.chaptertype == 1,
+        update_time: format_time(chapter.lastupdate),
+    }).collect();
+    ApiResponse::success("ok", Some(chapters))
+}
+
+/// 章节正文 与章节页读取方式一致 不分页 每段一行
+pub(crate) async fn legado_chapter(Path((id, cid)): Path<(u64, u64)>, headers: HeaderMap) -> ApiResponse<LegadoContent> {
+    let source_id = get_config().source_id(id);
+    // 隐藏的小说查不到信息 目录和正文也不能返回
+    if services::novel::get_novel_info(request_host(&headers), get_config().cache.info, source_id).await.is_empty() {
+        return ApiResponse::fail("小说不存在", None);
+    }
+    let chapter_rows = services::novel::get_chapter_rows(request_host(&headers), get_config().cache.info, source_id).await;
+    let Some(chapter) = chapter_rows.iter().find(|c| c.chapterid == cid) else {
+        return ApiResponse::fail("章节不存在", None);
+    };
+    let text = services::novel::get_chapter_text(source_id, chapter.source_id).await;
+    if text.is_empty() {
+        return ApiResponse::fail("章节正在手打中，请稍后重新访问！", None);
+    }
+    ApiResponse::success("ok", Some(LegadoContent {
+        title: chapter.chaptername.clone(),
+        content: text,
+    }))
+}
+
+fn legado_book(base: &str, row: &Novel) -> LegadoBook {
+    let cover_url = if row.img_url.starts_with('/') {
+        format!("{}{}", base, row.img_url)
+    } else {
+        row.img_url.clone()
+    };
+    LegadoBook {
+        name: row.articlename.clone(),
+        author: row.author.clone(),
+        intro: row.intro.trim().to_string(),
+        kind: format!("{},{}", row.sortname, row.isfull),
+        last_chapter: row.lastchapter.clone(),
+        cover_url,
+        book_url: format!("{}{}/book/{}", base, LEGADO_PREFIX, row.articleid),
+        toc_url: format!("{}{}/toc/{}", base, LEGADO_PREFIX, row.articleid),
+        web_url: format!("{}{}", base, row.info_url),
+        word_count: row.words.to_string(),
+        update_time: format_time(row.lastupdate),
+    }
+}
+
+fn format_time(time: u64) -> String {
+    match Local.timestamp_opt(time as i64, 0) {
+        chrono::LocalResult::Single(dt) => dt.format("%Y-%m-%d %H:%M").to_string(),
+        _ => String::new(),
+    }
+}
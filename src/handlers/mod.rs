@@ -10,3 +10,12 @@ pub(crate) mod history;
 pub(crate) mod search;
 pub(crate) mod user;
 pub(crate) mod admin;
+pub(crate) mod down;
+pub(crate) mod complete;
+pub(crate) mod report;
+pub(crate) mod sitemap;
+pub(crate) mod feed;
+pub(crate) mod legado;
+pub(crate) mod api;
+pub(crate) mod opds;
+pub(crate) mod captcha;
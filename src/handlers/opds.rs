@@ -0,0 +1,179 @@
+use axum::extract::{Path, Query};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use crate::models::novel::Novel;
+use crate::services;
+use crate::services::search::SearchDenied;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::redis::conn::get_cache_rows;
+use crate::utils::request::{client_ip, request_host, site_base_url};
+use crate::utils::text::{time_to_w3c, xml_escape};
+
+/// OPDS 目录 路径固定 不随伪静态变化
+const OPDS_PREFIX: &str = "/opds";
+const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpdsQuery {
+    q: Option<String>,
+    page: Option<u64>,
+}
+
+/// 根目录 最近更新、完本和各分类的导航
+pub(crate) async fn opds_root(headers: HeaderMap) -> Response {
+    let base = site_base_url(&headers);
+    let mut xml = feed_head(&base, "root", &get_config().site_name, OPDS_PREFIX, NAVIGATION_TYPE, timestamp_10() as u64);
+    let mut nav = vec![
+        ("latest".to_string(), "最近更新".to_string(), format!("{}/latest", OPDS_PREFIX)),
+        ("complete".to_string(), "完本小说".to_string(), format!("{}/complete", OPDS_PREFIX)),
+    ];
+    for sort in get_config().sort_arr.iter() {
+        nav.push((format!("sort:{}", sort.code), sort.caption.clone(), format!("{}/sort/{}", OPDS_PREFIX, sort.code)));
+    }
+    for (id, title, href) in nav {
+        xml.push_str("<entry>");
+        xml.push_str(&format!("<title>{}</title>", xml_escape(&title)));
+        xml.push_str(&format!("<id>{}</id>", xml_escape(&format!("{}{}#{}", base, OPDS_PREFIX, id))));
+        xml.push_str(&format!("<updated>{}</updated>", time_to_w3c(timestamp_10())));
+        xml.push_str(&format!("<link rel=\"subsection\" type=\"{}\" href=\"{}\"/>", ACQUISITION_TYPE, xml_escape(&href)));
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    opds_resp(xml, NAVIGATION_TYPE)
+}
+
+/// OpenSearch 描述 阅读器通过它知道搜索地址 必须是完整地址
+pub(crate) async fn opds_opensearch(headers: HeaderMap) -> Response {
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<OpenSearchDescription xmlns=\"http://a9.com/-/spec/opensearch/1.1/\">\n<ShortName>{name}</ShortName>\n<Description>{name} 书名或作者搜索</Description>\n<InputEncoding>UTF-8</InputEncoding>\n<OutputEncoding>UTF-8</OutputEncoding>\n<Url type=\"{kind}\" template=\"{base}{prefix}/search?q={{searchTerms}}\"/>\n</OpenSearchDescription>\n",
+        name = xml_escape(&get_config().site_name),
+        kind = xml_escape(ACQUISITION_TYPE),
+        base = xml_escape(&site_base_url(&headers)),
+        prefix = OPDS_PREFIX,
+    );
+    opds_resp(xml, "application/opensearchdescription+xml")
+}
+
+/// 搜索 与搜索页使用同一条查询 按IP限制搜索间隔和次数
+pub(crate) async fn opds_search(Query(params): Query<OpdsQuery>, headers: HeaderMap) -> Response {
+    let keyword = params.q.unwrap_or_default().trim().to_string();
+    let page = params.page.unwrap_or(1).max(1);
+    match services::search::check_search(&client_ip(&headers), &keyword, page, None, None).await {
+        Ok(()) => {}
+        Err(SearchDenied::Delay(_) | SearchDenied::TooFrequent) => return StatusCode::TOO_MANY_REQUESTS.into_response(),
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    let limit = get_config().search.limit.clamp(1, 100) as u64;
+    let (count, rows) = services::search::search_novels(request_host(&headers), &keyword, page, limit).await;
+    if page == 1 {
+        services::search::record_keyword(&keyword, !rows.is_empty()).await;
+    }
+    let href = format!("{}/search?q={}", OPDS_PREFIX, urlencoding::encode(&keyword));
+    acquisition_feed(&headers, &format!("搜索：{}", keyword), &href, &rows, page, count.div_ceil(limit).max(1))
+}
+
+/// 最近更新 与首页 lastupdate 使用同一条查询 只有一页
+pub(crate) async fn opds_latest(headers: HeaderMap) -> Response {
+    let rows = get_cache_rows(services::novel::lastupdate_sql(), request_host(&headers), get_config().cache.home as u64, None).await;
+    acquisition_feed(&headers, "最近更新", &format!("{}/latest", OPDS_PREFIX), &rows, 1, 1)
+}
+
+/// 完本小说
+pub(crate) async fn opds_complete(Query(params): Query<OpdsQuery>, headers: HeaderMap) -> Response {
+    let page = params.page.unwrap_or(1);
+    let (max_page, rows) = services::novel::get_complete_page(request_host(&headers), None, page).await;
+    if page == 0 || page > max_page {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    acquisition_feed(&headers, "完本小说", &format!("{}/complete", OPDS_PREFIX), &rows, page, max_page)
+}
+
+/// 分类
+pub(crate) async fn opds_sort(Path(code): Path<String>, Query(params): Query<OpdsQuery>, headers: HeaderMap) -> Response {
+    let Some(sort_index) = get_config().sort_arr.iter().position(|s| s.code == code) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let page = params.page.unwrap_or(1);
+    let (max_page, rows) = services::novel::get_sort_page(request_host(&headers), sort_index, page).await;
+    if page == 0 || page > max_page {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let title = get_config().sort_arr[sort_index].caption.clone();
+    acquisition_feed(&headers, &title, &format!("{}/sort/{}", OPDS_PREFIX, code), &rows, page, max_page)
+}
+
+/// 书籍列表 href 为不带页码的地址
+fn acquisition_feed(headers: &HeaderMap, title: &str, href: &str, rows: &[Novel], page: u64, max_page: u64) -> Response {
+    let base = site_base_url(headers);
+    let updated = rows.iter().map(|r| r.lastupdate).max().unwrap_or_else(|| timestamp_10() as u64);
+    let mut xml = feed_head(&base, href, title, &page_href(href, page), ACQUISITION_TYPE, updated);
+    if page > 1 {
+        xml.push_str(&format!("<link rel=\"previous\" type=\"{}\" href=\"{}\"/>\n", ACQUISITION_TYPE, xml_escape(&page_href(href, page - 1))));
+    }
+    if page < max_page {
+        xml.push_str(&format!("<link rel=\"next\" type=\"{}\" href=\"{}\"/>\n", ACQUISITION_TYPE, xml_escape(&page_href(href, page + 1))));
+    }
+    for row in rows {
+        xml.push_str(&book_entry(&base, row));
+    }
+    xml.push_str("</feed>\n");
+    opds_resp(xml, ACQUISITION_TYPE)
+}
+
+fn book_entry(base: &str, row: &Novel) -> String {
+    let img_url = if row.img_url.starts_with('/') {
+        format!("{}{}", base, row.img_url)
+    } else {
+        row.img_url.clone()
+    };
+    let mut xml = String::from("<entry>");
+    xml.push_str(&format!("<title>{}</title>", xml_escape(&row.articlename)));
+    xml.push_str(&format!("<id>{}</id>", xml_escape(&format!("{}{}", base, row.info_url))));
+    xml.push_str(&format!("<updated>{}</updated>", time_to_w3c(row.lastupdate as i64)));
+    xml.push_str(&format!("<author><name>{}</name><uri>{}</uri></author>", xml_escape(&row.author), xml_escape(&format!("{}{}", base, row.author_url))));
+    xml.push_str(&format!("<category term=\"{}\" label=\"{}\"/>", xml_escape(&row.sortname), xml_escape(&row.sortname)));
+    // intro_des 已经做过 HTML 转义
+    xml.push_str(&format!("<summary type=\"html\">{}</summary>", xml_escape(&row.intro_des)));
+    xml.push_str(&format!("<link rel=\"http://opds-spec.org/image\" href=\"{}\"/>", xml_escape(&img_url)));
+    xml.push_str(&format!("<link rel=\"http://opds-spec.org/image/thumbnail\" href=\"{}\"/>", xml_escape(&img_url)));
+    xml.push_str(&format!("<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>", xml_escape(&row.info_url)));
+    if get_config().enable_down {
+        if !get_config().rewrite.down_url.is_empty() {
+            xml.push_str(&format!("<link rel=\"http://opds-spec.org/acquisition\" type=\"text/plain\" href=\"{}\"/>", xml_escape(&get_config().down_url(row.articleid))));
+        }
+        if !get_config().rewrite.epub_url.is_empty() {
+            xml.push_str(&format!("<link rel=\"http://opds-spec.org/acquisition\" type=\"application/epub+zip\" href=\"{}\"/>", xml_escape(&get_config().epub_url(row.articleid))));
+        }
+    }
+    xml.push_str("</entry>\n");
+    xml
+}
+
+/// feed 开头 包含 start/self/search 链接 不含结束标签
+fn feed_head(base: &str, id: &str, title: &str, self_href: &str, kind: &str, updated: u64) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:opds=\"http://opds-spec.org/2010/catalog\">\n");
+    xml.push_str(&format!("<id>{}</id>\n", xml_escape(&format!("{}{}#{}", base, OPDS_PREFIX, id))));
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(title)));
+    xml.push_str(&format!("<updated>{}</updated>\n", time_to_w3c(updated as i64)));
+    xml.push_str(&format!("<author><name>{}</name><uri>{}</uri></author>\n", xml_escape(&get_config().site_name), xml_escape(base)));
+    xml.push_str(&format!("<link rel=\"start\" type=\"{}\" href=\"{}\"/>\n", NAVIGATION_TYPE, OPDS_PREFIX));
+    xml.push_str(&format!("<link rel=\"self\" type=\"{}\" href=\"{}\"/>\n", kind, xml_escape(self_href)));
+    xml.push_str(&format!("<link rel=\"search\" type=\"application/opensearchdescription+xml\" href=\"{}/opensearch.xml\"/>\n", OPDS_PREFIX));
+    xml
+}
+
+fn page_href(href: &str, page: u64) -> String {
+    if page <= 1 {
+        return href.to_string();
+    }
+    let sep = if href.contains('?') { '&' } else { '?' };
+    format!("{}{}page={}", href, sep, page)
+}
+
+fn opds_resp(s: String, content_type: &str) -> Response {
+    ([(CONTENT_TYPE, format!("{}; charset=utf-8", content_type))], s).into_response()
+}
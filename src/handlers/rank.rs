@@ -3,30 +3,24 @@ use axum::extract::{OriginalUri, Path, State};
 use axum::http::{HeaderMap};
 use axum::http::header::HOST;
 use axum::response::IntoResponse;
-use sea_orm::{Value, Values};
 use serde::{Deserialize, Serialize};
 use crate::{routes, services, utils};
 use crate::models::novel::Novel;
-use crate::services::novel::extract_str;
+use crate::handlers::index_list::IndexListPageUrl;
+use crate::services::novel::{extract_id, extract_str, generate_pagination_numbers};
 use crate::utils::conf::get_config;
 use crate::utils::file::file_exists;
 use crate::utils::templates::render;
 use crate::utils::templates::render::TeraRenderError;
 
-const RANK_NAV_ITEMS: &[(&str, &str)] = &[
-    ("allvisit", "总排行榜"),
-    ("monthvisit", "月排行榜"),
-    ("weekvisit", "周排行榜"),
-    ("dayvisit", "日排行榜"),
-    ("allvote", "总推荐榜"),
-    ("monthvote", "月推荐榜"),
-    ("weekvote", "周推荐榜"),
-    ("dayvote", "日推荐榜"),
-    ("goodnum", "收藏榜"),
-];
+/// {sort} 取这个值时表示不按分类筛选
+pub(crate) const RANK_ALL: &str = "all";
 
-fn get_rank_nav(key: &str) -> Option<&str> {
-    RANK_NAV_ITEMS.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+#[derive(Deserialize)]
+pub(crate) struct RankPath {
+    code: String,
+    sort: Option<String>,
+    page: Option<String>,
 }
 
 #[derive(Deserialize,Serialize)]
@@ -36,8 +30,16 @@ struct RankUrls {
     select: bool,
 }
 
+/// 排行榜链接 第一页且不筛选分类时使用 rank_url 其余使用 rank_page_url
+pub(crate) fn rank_link(code: &str, sort: &str, page: usize) -> String {
+    if get_config().rewrite.rank_page_url.is_empty() || (sort == RANK_ALL && page == 1) {
+        return get_config().rank_url(code);
+    }
+    get_config().rank_page_url(code, sort, page)
+}
+
 pub(crate) async fn get_rank(
-    Path(code): Path<String>,
+    Path(p): Path<RankPath>,
     State(app_state): State<routes::app::AppState>,
     headers: HeaderMap,
     OriginalUri(uri): OriginalUri,
@@ -46,38 +48,98 @@ pub(crate) async fn get_rank(
         println!("No such file or directory");
         return Err(TeraRenderError::InvalidId);
     }
-    let key = extract_str(&code).unwrap_or("");
-    if key.is_empty() {
-        return Err(TeraRenderError::InvalidId);
+    let key = extract_str(&p.code).unwrap_or("");
+    // 排序字段只能来自白名单 不能作为参数绑定 否则 MySQL 会按常量排序
+    let rank = get_config().get_rank(key).cloned().ok_or(TeraRenderError::InvalidId)?;
+    let mut page = 1;
+    if let Some(s) = p.page {
+        page = extract_id(&s).ok_or(TeraRenderError::InvalidId)?;
     }
-    let title =  get_rank_nav(key).unwrap_or("");
-    if title.is_empty() {
+    if page == 0 {
         return Err(TeraRenderError::InvalidId);
     }
+    let code = match p.sort {
+        Some(s) => extract_str(&s).ok_or(TeraRenderError::InvalidId)?.to_string(),
+        None => RANK_ALL.to_string(),
+    };
+    let sort_id = if code == RANK_ALL {
+        None
+    } else {
+        Some(get_config().sort_arr.iter()
+            .position(|s| s.code == code)
+            .ok_or(TeraRenderError::InvalidId)?)
+    };
     let url = headers
         .get(HOST)
         .and_then(|v| v.to_str().ok()) // 安全转换为字符串
         .unwrap_or("unknown.host");
-    let rows = utils::redis::conn::get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article WHERE {where} ORDER BY ? DESC LIMIT 100;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where()),
-        url,
-        get_config().cache.rank as u64,
-        Some(Values(vec![Value::String(Some(code.to_owned()))])),
-    ).await;
-    // 开始生成nav
+    let (mut max_page, rows) = services::novel::get_rank_page(url, &rank, sort_id, page).await;
+    if page > max_page {
+        return Err(TeraRenderError::InvalidId);
+    }
+    // 开始生成nav 切换排行榜时保留分类筛选
     let mut rank_urls = Vec::new();
-    for (k, v) in RANK_NAV_ITEMS {
+    for r in get_config().rank_arr.iter() {
         rank_urls.push(RankUrls {
-            title: v.to_string(),
-            url:  get_config().rank_url(k),
-            select: *k == key,
+            title: r.caption.clone(),
+            url: rank_link(&r.code, &code, 1),
+            select: r.code == rank.code,
+        });
+    }
+    // 分类筛选 只有伪静态里有{sort}时才生成
+    let mut sort_nav = Vec::new();
+    if get_config().rewrite.rank_page_url.contains("{sort}") {
+        sort_nav.push(RankUrls {
+            title: "全部".to_string(),
+            url: rank_link(&rank.code, RANK_ALL, 1),
+            select: sort_id.is_none(),
         });
+        for (i, s) in get_config().sort_arr.iter().enumerate() {
+            sort_nav.push(RankUrls {
+                title: s.caption.clone(),
+                url: rank_link(&rank.code, &s.code, 1),
+                select: sort_id == Some(i),
+            });
+        }
+    }
+    let mut prev_url = String::new();
+    let mut next_url = String::new();
+    let mut jump_pages: Vec<IndexListPageUrl> = Vec::new();
+    if !get_config().rewrite.rank_page_url.is_empty() {
+        if page > 1 {
+            prev_url = rank_link(&rank.code, &code, (page - 1) as usize);
+        }
+        if page < max_page {
+            next_url = rank_link(&rank.code, &code, (page + 1) as usize);
+        }
+        for p in generate_pagination_numbers(page as usize, max_page) {
+            jump_pages.push(IndexListPageUrl {
+                page: p as u64,
+                url: rank_link(&rank.code, &code, p),
+                select: p == page as usize,
+            });
+        }
+    } else {
+        max_page = 1;   // 没有分页伪静态时只显示第一页
     }
+    let sort = sort_id.map(|i| get_config().sort_arr[i].clone());
+    let title = match &sort {
+        Some(s) => format!("{}{}", s.caption, rank.caption),
+        None => rank.caption.clone(),
+    };
     let mut ctx = tera::Context::new();
     services::novel::process_tera_tag(&headers, &uri, &mut ctx);
     ctx.insert("title", &title);
+    ctx.insert("rank", &rank);
+    ctx.insert("sort", &sort);
     ctx.insert("rows", &rows);
     ctx.insert("rank_nav", &rank_urls);
+    ctx.insert("sort_nav", &sort_nav);
+    ctx.insert("prev_url", &prev_url);
+    ctx.insert("next_url", &next_url);
+    ctx.insert("page", &page);
+    ctx.insert("max_page", &max_page);
+    ctx.insert("jump_pages", &jump_pages);
     let template_path = format!("{}/rank.html", get_config().theme_dir);
     let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
     Ok((
@@ -134,10 +196,10 @@ pub(crate) async fn get_top(
         rows.push(sort_rank_map);
     }
     let mut rank_urls = Vec::new();
-    for (k, v) in RANK_NAV_ITEMS {
+    for r in conf.rank_arr.iter() {
         rank_urls.push(RankUrls {
-            title: v.to_string(),
-            url:  get_config().rank_url(k),
+            title: r.caption.clone(),
+            url:  get_config().rank_url(&r.code),
             select: false,
         });
     }
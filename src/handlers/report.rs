@@ -0,0 +1,73 @@
+use axum::extract::Form;
+use axum::http::HeaderMap;
+use axum::http::header::HOST;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use crate::services::json::ApiResponse;
+use crate::services::novel::{get_chapter_rows, get_novel_info};
+use crate::services::report::{add_report, NewReport, ReportError};
+use crate::utils;
+use crate::utils::conf::get_config;
+use crate::utils::cookie::is_cookie_exist;
+use crate::utils::request::client_fingerprint;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReportReq {
+    articleid: u64,
+    chapterid: u64,
+    reason: String,
+}
+
+/// 章节报错 articleid 和 chapterid 为页面上的ID
+pub(crate) async fn post_report(
+    mut jar: CookieJar,
+    headers: HeaderMap,
+    Form(params): Form<ReportReq>,
+) -> Response {
+    if !get_config().is_report {
+        return ApiResponse::<String>::fail("报错失败", Some(vec!["对不起,管理员已关闭此功能".to_string()])).into_response();
+    }
+    if is_cookie_exist(&jar, "report_last_time") {
+        return ApiResponse::<String>::fail("报错失败", Some(vec![format!("报错间隔: {} 秒", get_config().report_time)])).into_response();
+    }
+    if params.articleid == 0 || params.chapterid == 0 || params.reason.trim().is_empty() {
+        return ApiResponse::<String>::fail("报错失败", Some(vec!["传参错误".to_string()])).into_response();
+    }
+    let url = headers
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown.host");
+    let source_id = get_config().source_id(params.articleid);
+    // 章节名从章节列表取 不信任客户端传来的内容
+    let chapter_rows = get_chapter_rows(url, get_config().cache.info, source_id).await;
+    let Some(chapter) = chapter_rows.iter().find(|c| c.chapterid == params.chapterid && c.chaptertype != 1) else {
+        return ApiResponse::<String>::fail("报错失败", Some(vec!["章节不存在".to_string()])).into_response();
+    };
+    let Some(row) = get_novel_info(url, get_config().cache.info, source_id).await.into_iter().next() else {
+        return ApiResponse::<String>::fail("报错失败", Some(vec!["小说不存在".to_string()])).into_response();
+    };
+    let fingerprint = client_fingerprint(&headers);
+    let res = add_report(NewReport {
+        articleid: source_id,
+        articlename: &row.articlename,
+        chapterid: chapter.source_id,
+        chaptername: &chapter.chaptername,
+        reason: &params.reason,
+        fingerprint: &fingerprint,
+        host: url,
+    }).await;
+    match res {
+        Ok(_) => {
+            if get_config().report_time > 0 {
+                jar = utils::cookie::set_cookie_value(jar, "report_last_time", "1", get_config().report_time as usize, true, true);
+            }
+            (jar, ApiResponse::success("报错成功", Some(""))).into_response()
+        }
+        Err(ReportError::DbErr(e)) => {
+            eprintln!("add_report error: {e:?}");
+            ApiResponse::<String>::fail("报错失败", Some(vec!["数据库操作失败".to_string()])).into_response()
+        }
+        Err(e) => ApiResponse::<String>::fail("报错失败", Some(vec![e.to_string()])).into_response(),
+    }
+}
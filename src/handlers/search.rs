@@ -1,5 +1,7 @@
 use crate::models::novel::Novel;
-use crate::utils::cookie::is_cookie_exist;
+use crate::handlers::index_list::IndexListPageUrl;
+use crate::services::novel::generate_pagination_numbers;
+use crate::services::search::{SearchDenied, SEARCH_LAST_COOKIE};
 use crate::utils::templates::render;
 use crate::utils::templates::render::TeraRenderError;
 use crate::{routes, services, utils};
@@ -8,15 +10,15 @@ use axum::http::header::{CONTENT_TYPE, HOST};
 use axum::http::{HeaderMap, Uri};
 use axum::response::{Html, IntoResponse, Response};
 use axum_extra::extract::CookieJar;
-use sea_orm::{Value, Values};
 use serde::Deserialize;
-use unicode_segmentation::UnicodeSegmentation;
 use crate::utils::conf::get_config;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct SearchQuery {
     pub keyword: String,
-    pub page: Option<u64>, // 可以不存在 预留吧
+    pub page: Option<u64>, // 不存在时为第一页
+    pub captcha: Option<String>,
+    pub captcha_id: Option<String>,
 }
 
 pub(crate) async fn get_search(
@@ -26,7 +28,7 @@ pub(crate) async fn get_search(
     OriginalUri(uri): OriginalUri,
     jar: CookieJar,
 ) -> Result<impl IntoResponse, TeraRenderError> {
-    search(params.keyword, params.page, app_state, headers, uri, jar).await
+    search(params, app_state, headers, uri, jar).await
 }
 
 #[allow(dead_code)]
@@ -37,17 +39,27 @@ pub(crate) async fn post_search(
     jar: CookieJar,
     Form(params): Form<SearchQuery>,
 ) -> Result<impl IntoResponse, TeraRenderError> {
-    search(params.keyword, params.page, app_state, headers, uri, jar).await
+    search(params, app_state, headers, uri, jar).await
 }
 
 pub(crate) fn html_resp(s: String) -> Response {
     ([(CONTENT_TYPE, "text/html; charset=utf-8")], Html(s)).into_response()
 }
 
+/// 搜索太频繁时的验证码页面 通过后带着原关键字重新搜索
+fn captcha_page(action: &str, keyword: &str, msg: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1"><title>请输入验证码</title></head><body style="text-align:center;padding-top:80px"><form method="get" action="{}"><p>{}</p><input type="hidden" name="keyword" value="{}"><p>{}</p><input type="text" name="captcha" maxlength="8" autocomplete="off" required autofocus> <button type="submit">继续搜索</button></form></body></html>"#,
+        html_escape::encode_double_quoted_attribute(action),
+        msg,
+        html_escape::encode_double_quoted_attribute(keyword),
+        services::captcha::captcha_html()
+    )
+}
+
 /// 通用处理
 async fn search(
-    keyword: String,
-    _page: Option<u64>,
+    params: SearchQuery,
     app_state: routes::app::AppState,
     headers: HeaderMap,
     uri: Uri,
@@ -56,50 +68,79 @@ async fn search(
     if get_config().search.delay == -1 {
         return Ok(html_resp(r#"<script>alert("对不起,管理员已关闭此功能.");window.history.go(-1);</script>"#.to_owned()));
     }
-    if is_cookie_exist(&jar, "search_last_time") {
-        let r = format!(r#"<script>alert("搜索间隔: {} 秒");window.history.go(-1);</script>"#, get_config().search.delay);
-        return Ok(html_resp(r));
-    }
-    let limit = get_config().search.limit.min(100);   // 搜索结果最多显示100条
+    let SearchQuery { keyword, page, captcha, captcha_id } = params;
+    let page = page.unwrap_or(1).max(1);
+    let limit = get_config().search.limit.clamp(1, 100);   // 每页最多显示100条
     let mut search_rows: Vec<Novel> = Vec::new();
     let mut search_no_rows : Vec<Novel> = Vec::new();
+    let mut count = 0;
+    let mut max_page = 1;
     let url = headers
         .get(HOST)
         .and_then(|v| v.to_str().ok()) // 安全转换为字符串
         .unwrap_or("unknown.host");
     if !keyword.is_empty() {
-        if keyword.graphemes(true).count() < get_config().search.min as usize {
-            let r = format!(r#"<script>alert("关键字最少 {} 个字符");window.history.go(-1);</script>"#, get_config().search.min);
-            return Ok(html_resp(r));
+        let ip = utils::request::client_ip(&headers);
+        let form_captcha = captcha_id.as_deref().map(|id| (id, captcha.as_deref()));
+        match services::search::check_search(&ip, &keyword, page, Some(&jar), form_captcha).await {
+            Ok(()) => {}
+            Err(SearchDenied::TooFrequent) => {
+                return Ok(html_resp(captcha_page(uri.path(), &keyword, "搜索太频繁，请输入验证码后继续")));
+            }
+            Err(e @ SearchDenied::CaptchaFailed) => {
+                return Ok(html_resp(captcha_page(uri.path(), &keyword, &e.to_string())));
+            }
+            Err(e) => {
+                let r = format!(r#"<script>alert("{}");window.history.go(-1);</script>"#, e);
+                return Ok(html_resp(r));
+            }
         }
-        let search_key = Value::String(Some(keyword.clone()));
-        search_rows = if get_config().sys_ver > 6.0f32 {    // 多选搜索兼容
-            utils::redis::conn::get_cache_rows(
-                format!("SELECT {filed} FROM {table}article_article WHERE {where} AND MATCH(articlename, author) AGAINST(CONCAT('+',?) IN BOOLEAN MODE) ORDER BY lastupdate DESC LIMIT {limit};", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), limit = limit),
-                url,
-                get_config().search.time as u64,
-                Some(Values(vec![search_key.clone()])),
-            ).await
-        } else {
-             utils::redis::conn::get_cache_rows(
-                format!("SELECT {filed} FROM {table}article_article WHERE {where} AND (articlename LIKE CONCAT('%',?, '%') OR author LIKE CONCAT('%',?, '%')) ORDER BY lastupdate DESC LIMIT {limit};", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), limit = limit),
-                url,
-                get_config().search.time as u64,
-                Some(Values(vec![search_key.clone(),search_key.clone()])),
-            ).await
-        };
-        if get_config().search.delay > 0 {
-            jar = utils::cookie::set_cookie_value(jar, "search_last_time", "1", get_config().search.delay as usize, true, true);
+        (count, search_rows) = services::search::search_novels(url, &keyword, page, limit as u64).await;
+        max_page = count.div_ceil(limit as u64).max(1);
+        if page > max_page {
+            return Err(TeraRenderError::InvalidId);
+        }
+        if page == 1 {  // 翻页不重复记录
+            services::search::record_keyword(&keyword, !search_rows.is_empty()).await;
+        }
+        if get_config().search.delay > 0 && page == 1 {
+            let keyword_hash = services::search::keyword_hash(&keyword);
+            jar = utils::cookie::set_cookie_value(jar, SEARCH_LAST_COOKIE, &keyword_hash, get_config().search.delay as usize, true, true);
         }
     }
     if search_rows.is_empty() {
         search_no_rows = services::novel::common_novel_random(url, limit, get_config().search.time as u64).await;
     }
+    let prev_url = if page > 1 {
+        get_config().search_url(&keyword, (page - 1) as usize)
+    } else {
+        String::new()
+    };
+    let next_url = if page < max_page {
+        get_config().search_url(&keyword, (page + 1) as usize)
+    } else {
+        String::new()
+    };
+    let mut jump_pages: Vec<IndexListPageUrl> = Vec::new();
+    let j = generate_pagination_numbers(page as usize, max_page);
+    for p in j {
+        jump_pages.push(IndexListPageUrl {
+            page: p as u64,
+            url: get_config().search_url(&keyword, p),
+            select: p == page as usize,
+        });
+    }
     let mut ctx = tera::Context::new();
     services::novel::process_tera_tag(&headers, &uri, &mut ctx);
     ctx.insert("keyword", &keyword);
     ctx.insert("rows", &search_rows);
     ctx.insert("search_no_rows", &search_no_rows);
+    ctx.insert("count", &count);
+    ctx.insert("prev_url", &prev_url);
+    ctx.insert("next_url", &next_url);
+    ctx.insert("page", &page);
+    ctx.insert("max_page", &max_page);
+    ctx.insert("jump_pages", &jump_pages);
     let template_path = format!("{}/search.html", get_config().theme_dir);
     let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
     let resp = ([(CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response();
@@ -0,0 +1,51 @@
+use axum::extract::Path;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use crate::services::novel::extract_id;
+use crate::services::sitemap::{get_sitemap, get_sitemap_index};
+use crate::utils::request::trusted_base_url;
+
+#[derive(Deserialize)]
+pub(crate) struct SitemapPath {
+    kind: String,
+    page: String,
+}
+
+/// sitemap 索引 /sitemap.xml
+pub(crate) async fn get_sitemap_xml(headers: HeaderMap) -> Response {
+    // 缓存和地址都跟着 Host 走 只认 site_url 和 domains
+    let Some(base) = trusted_base_url(&headers) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match get_sitemap_index(&base).await {
+        Ok(xml) => xml_resp(xml),
+        Err(e) => {
+            eprintln!("生成 sitemap 索引失败：{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// 子 sitemap /sitemap/{kind}/{page}.xml kind 为 novel sort chapter lang
+pub(crate) async fn get_sitemap_page(Path(p): Path<SitemapPath>, headers: HeaderMap) -> Response {
+    let Some(page) = extract_id(&p.page) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(base) = trusted_base_url(&headers) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match get_sitemap(&base, &p.kind, page).await {
+        Ok(Some(xml)) => xml_resp(xml),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            eprintln!("生成 sitemap 失败：{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub(crate) fn xml_resp(s: String) -> Response {
+    ([(CONTENT_TYPE, "application/xml; charset=utf-8")], s).into_response()
+}
@@ -2,7 +2,6 @@ use axum::extract::{OriginalUri, Path, State};
 use axum::http::{HeaderMap};
 use axum::http::header::HOST;
 use axum::response::IntoResponse;
-use sea_orm::{Value, Values};
 use serde::{Deserialize};
 use crate::{routes, services};
 use crate::handlers::index_list::IndexListPageUrl;
@@ -11,7 +10,6 @@ use crate::utils::conf::get_config;
 use crate::utils::file::file_exists;
 use crate::utils::templates::render;
 use crate::utils::templates::render::TeraRenderError;
-use crate::utils::redis::conn::{get_cache_rows,get_cache_count};
 
 #[derive(Deserialize)]
 pub(crate) struct SortPath {
@@ -51,26 +49,10 @@ pub(crate) async fn get_sort(
         .and_then(|v| v.to_str().ok()) // 安全转换为字符串
         .unwrap_or("unknown.host");
     let sort = &get_config().sort_arr[sort_id];
-    let offset = (page - 1).saturating_mul(get_config().category_per_page as u64);
-    let count = get_cache_count(
-        format!("SELECT COUNT(*) AS cnt FROM {table}article_article WHERE {where} AND sortid = ?;", table = get_config().prefix, where = get_config().get_where()),
-        url,
-        get_config().cache.sort as u64,
-        Some(Values(vec![Value::TinyInt(Some((sort_id + 1) as i8))])),
-    ).await;
-    let mut max_page = count.div_ceil(get_config().category_per_page as u64).max(1);
-    if get_config().category_max_page > 0 && max_page > get_config().category_max_page {    // 如果启用了访问深度 则此处生效
-        max_page = get_config().category_max_page;
-    }
+    let (max_page, rows) = services::novel::get_sort_page(url, sort_id, page).await;
     if page > max_page {    // 如不需要超出边界404 可注释
         return Err(TeraRenderError::InvalidId);
     }
-    let rows = get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND sortid = ? ORDER BY lastupdate DESC LIMIT {limit} OFFSET ?;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), limit = get_config().category_per_page),
-        url,
-        get_config().cache.sort as u64,
-        Some(Values(vec![((sort_id + 1) as i8).into(), offset.into()])),
-    ).await;
     let prev_url = if page > 1 {
         get_config().sort_url(sort.code.as_str(), sort_id + 1, (page - 1) as usize)
     } else {
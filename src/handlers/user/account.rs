@@ -0,0 +1,174 @@
+use axum::extract::{Form, OriginalUri, State};
+use axum::Extension;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use serde_json::json;
+use crate::{routes, services};
+use crate::handlers::user::history::history_items;
+use crate::handlers::user::register::PASSWORD_RE;
+use crate::models::user::{User, UserSession};
+use crate::services::json::ApiResponse;
+use crate::services::user::{timestamp_10, CreateUserError};
+use crate::utils::conf::get_config;
+use crate::utils::file::file_exists;
+use crate::utils::request::client_ip;
+use crate::utils::templates::render;
+use crate::utils::templates::render::TeraRenderError;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangePasswordForm {
+    old_password: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeleteAccountForm {
+    password: String,
+}
+
+/// 账号中心
+pub(crate) async fn get_account(
+    State(app_state): State<routes::app::AppState>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    Extension(session): Extension<UserSession>,
+) -> Result<impl IntoResponse, TeraRenderError> {
+    if !file_exists(format!("templates/{}/user/account.html", get_config().theme_dir)) {
+        println!("No such file or directory");
+        return Err(TeraRenderError::InvalidId);
+    }
+    let user = services::user::get_user_by_id(&session.userid.to_string()).await.map_err(|e| {
+        eprintln!("get_user_by_id error: {e:?}");
+        TeraRenderError::InvalidId
+    })?;
+    let verified = services::email::is_email_verified(user.uid).await.unwrap_or_else(|e| {
+        eprintln!("is_email_verified error: {e:?}");
+        false
+    });
+    let mut ctx = tera::Context::new();
+    services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    // 不把密码字段放进模板
+    ctx.insert("user", &json!({
+        "uid": user.uid,
+        "uname": user.uname,
+        "name": user.name,
+        "email": user.email,
+    }));
+    ctx.insert("email_verified", &verified);
+    let template_path = format!("{}/user/account.html", get_config().theme_dir);
+    let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
+    Ok((
+        [(CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    ))
+}
+
+/// 校验当前用户的密码 输错和登录一样计入失败次数
+pub(crate) async fn check_password(headers: &HeaderMap, session: &UserSession, password: &str) -> Result<User, String> {
+    let ip = client_ip(headers);
+    if let Some(wait) = services::login_limit::check_login(&ip, &session.uname).await {
+        return Err(format!("尝试次数过多，请 {} 秒后再试", wait));
+    }
+    match services::user::get_user(&session.uname, password).await {
+        Ok(user) => Ok(user),
+        Err(CreateUserError::PasswordError) => {
+            services::login_limit::record_failure(&ip, &session.uname).await;
+            Err("密码错误".to_string())
+        }
+        Err(e) => {
+            eprintln!("get_user error: {e:?}");
+            Err(e.to_string())
+        }
+    }
+}
+
+/// 修改密码 其他设备上的登录全部失效 当前设备换一个新会话
+pub(crate) async fn post_account_password(
+    mut jar: CookieJar,
+    headers: HeaderMap,
+    Extension(session): Extension<UserSession>,
+    Form(params): Form<ChangePasswordForm>,
+) -> impl IntoResponse {
+    if !PASSWORD_RE.is_match(&params.password) {
+        return (jar, ApiResponse::fail("参数校验失败", Some(vec!["密码必须是6-32位".to_string()])));
+    }
+    let user = match check_password(&headers, &session, &params.old_password).await {
+        Ok(user) => user,
+        Err(msg) => return (jar, ApiResponse::fail("修改失败", Some(vec![msg]))),
+    };
+    if let Err(e) = services::password::change_password(&user, &params.password).await {
+        eprintln!("change_password error: {e:?}");
+        return (jar, ApiResponse::fail("修改失败", Some(vec!["数据库操作失败".to_string()])));
+    }
+    if let Err(e) = services::session::delete_user_sessions(user.uid).await {
+        eprintln!("delete_user_sessions error: {e:?}");
+    }
+    match services::session::create_session(&user, &headers).await {
+        Ok(session) => jar = services::session::login_cookies(jar, &headers, &session),
+        Err(e) => {
+            eprintln!("create_session error: {e:?}");
+            return (services::session::logout_cookies(jar), ApiResponse::success("密码已修改，请重新登录", Some("")));
+        }
+    }
+    (jar, ApiResponse::success("密码已修改，其他设备需要重新登录", Some("")))
+}
+
+/// 注销账号 需要输入密码
+pub(crate) async fn post_account_delete(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Extension(session): Extension<UserSession>,
+    Form(params): Form<DeleteAccountForm>,
+) -> impl IntoResponse {
+    let user = match check_password(&headers, &session, &params.password).await {
+        Ok(user) => user,
+        Err(msg) => return (jar, ApiResponse::fail("注销失败", Some(vec![msg]))),
+    };
+    if let Err(e) = services::account::delete_account(&user).await {
+        eprintln!("delete_account error: {e:?}");
+        return (jar, ApiResponse::fail("注销失败", Some(vec!["数据库操作失败".to_string()])));
+    }
+    (services::session::logout_cookies(jar), ApiResponse::success("账号已注销", Some("")))
+}
+
+/// 导出书架和阅读记录 JSON 文件下载
+pub(crate) async fn get_account_export(Extension(session): Extension<UserSession>) -> Response {
+    let user = match services::user::get_user_by_id(&session.userid.to_string()).await {
+        Ok(user) => user,
+        Err(e) => {
+            eprintln!("get_user_by_id error: {e:?}");
+            return ApiResponse::<()>::fail("导出失败", Some(vec!["用户不存在".to_string()])).into_response();
+        }
+    };
+    let bookcase = services::account::get_bookcase_export(user.uid).await;
+    let history = services::history::get_history_list(user.uid).await;
+    let (bookcase, history) = match (bookcase, history) {
+        (Ok(bookcase), Ok(history)) => (bookcase, history),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("export error: {e:?}");
+            return ApiResponse::<()>::fail("导出失败", Some(vec!["数据库操作失败".to_string()])).into_response();
+        }
+    };
+    let data = json!({
+        "user": {
+            "uid": user.uid,
+            "uname": user.uname,
+            "name": user.name,
+            "email": user.email,
+        },
+        "bookcase": bookcase,
+        "history": history_items(&history),
+        "exported": timestamp_10(),
+    });
+    let body = serde_json::to_string_pretty(&data).unwrap_or_default();
+    (
+        [
+            (CONTENT_TYPE, "application/json; charset=utf-8".to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"account-{}.json\"", user.uid)),
+        ],
+        body,
+    ).into_response()
+}
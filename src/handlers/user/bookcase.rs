@@ -1,42 +1,47 @@
 use axum::body::Body;
-use axum::extract::{OriginalUri, State, Form};
+use axum::extract::{OriginalUri, State, Form, Query};
+use axum::Extension;
 use axum::http::{HeaderMap, Request};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Redirect, Response};
 use axum_extra::extract::{CookieJar };
-use cookie::Cookie;
 use sea_orm::Values;
 use serde::Deserialize;
 use crate::{routes, services};
+use crate::models::user::UserSession;
 use crate::services::json::ApiResponse;
-use crate::services::user::get_bookcase_list;
+use crate::services::user::{get_bookcase_list, get_bookcase_update_count, BookcaseOrder};
+use crate::utils::request::request_host;
 use crate::utils::conf::{get_config};
 use crate::utils::db::db::{exec_sql, query_count};
 use crate::utils::file::file_exists;
 use crate::utils::templates::render;
 use crate::utils::templates::render::TeraRenderError;
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct BookcaseQuery {
+    order: Option<String>,
+}
+
 pub(crate) async fn get_bookcase(
     State(app_state): State<routes::app::AppState>,
     headers: HeaderMap,
     OriginalUri(uri): OriginalUri,
-    jar: CookieJar
+    Query(params): Query<BookcaseQuery>,
+    Extension(session): Extension<UserSession>,
 ) -> Result<Response, TeraRenderError> {
     // 有中间件 不存在文件自然重定向
-    let user_id = jar.get("ss_userid").map(|c| c.value().to_string());
-    let Some(user_id) = user_id else {    // 通常不会出问题,中间件校验过了 出问题直接重定向吧
-        return Ok(Redirect::to("/login").into_response());
-    };
-    if user_id.is_empty() {
-        return Ok(Redirect::to("/login").into_response());
-    }
-    let bs = get_bookcase_list(user_id.as_str()).await.unwrap_or_else(|e| {
+    let user_id = session.userid.to_string();
+    // 默认按小说更新时间排序
+    let order = params.order.as_deref().and_then(BookcaseOrder::from_code).unwrap_or(BookcaseOrder::Update);
+    let bs = get_bookcase_list(request_host(&headers), user_id.as_str(), order).await.unwrap_or_else(|e| {
         eprintln!("get_bookcase_list error: {e:?}");
         vec![]
     });
     let mut ctx = tera::Context::new();
     services::novel::process_tera_tag(&headers, &uri, &mut ctx);
     ctx.insert("bookcase_list", &bs);
+    ctx.insert("bookcase_order", params.order.as_deref().filter(|o| BookcaseOrder::from_code(o).is_some()).unwrap_or("update"));
     let template_path = format!("{}/user/bookcase.html", get_config().theme_dir);
     let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
     Ok((
@@ -45,6 +50,17 @@ pub(crate) async fn get_bookcase(
     ).into_response())
 }
 
+/// 书架有更新的小说数 供页头角标使用
+pub(crate) async fn get_bookcase_updates(Extension(session): Extension<UserSession>) -> ApiResponse<u64> {
+    match get_bookcase_update_count(&session.userid.to_string()).await {
+        Ok(num) => ApiResponse::success("ok", Some(num)),
+        Err(e) => {
+            eprintln!("sql error: {e:?}");
+            ApiResponse::fail("获取失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct DelBookcaseReq {
     caseid: u64
@@ -59,13 +75,13 @@ pub(crate) struct AddBookcaseReq {
 }
 
 pub(crate) async fn add_bookcase(
-    jar: CookieJar,
+    Extension(session): Extension<UserSession>,
     Form(params): Form<AddBookcaseReq>,
 ) -> impl IntoResponse {
     if !file_exists(format!("templates/{}/user/bookcase.html", get_config().theme_dir)) {
         return Err(TeraRenderError::InvalidId);
     }
-    let user_id = jar.get("ss_userid").map(|c| c.value().to_string());
+    let user_id = Some(session.userid.to_string());
     if params.articleid == 0 || params.articlename.is_empty() || user_id.is_none(){
         return Ok(ApiResponse::fail("添加失败", Some(vec!["传参错误".to_string()])))
     }
@@ -115,13 +131,13 @@ pub(crate) async fn add_bookcase(
 }
 
 pub(crate) async fn del_bookcase(
-    jar: CookieJar,
+    Extension(session): Extension<UserSession>,
     Form(params): Form<DelBookcaseReq>,
 ) -> impl IntoResponse {
     if !file_exists(format!("templates/{}/user/bookcase.html", get_config().theme_dir)) {
         return Err(TeraRenderError::InvalidId);
     }
-    let user_id = jar.get("ss_userid").map(|c| c.value().to_string());
+    let user_id = Some(session.userid.to_string());
     if params.caseid == 0 || user_id.is_none() {
         return Ok(ApiResponse::fail("删除失败", Some(vec!["传参错误".to_string()])))
     }
@@ -142,27 +158,20 @@ pub(crate) async fn del_bookcase(
 
 pub(crate) async fn login_auth(
     jar: CookieJar,
-    req: Request<Body>,
+    headers: HeaderMap,
+    mut req: Request<Body>,
     next: Next,
 ) -> Result<Response, TeraRenderError> {
     if !file_exists(format!("templates/{}/user/bookcase.html", get_config().theme_dir)) {
         return Err(TeraRenderError::InvalidId);
     }
-    let user_id = jar.get("ss_userid").map(|c| c.value().to_string());
-    let pass = jar.get("ss_password").map(|c| c.value().to_string());
-    let (Some(user_id), Some(pass)) = (user_id, pass) else {
-        return Ok(Redirect::to("/login").into_response());
-    };
-    match services::user::is_user_login(&user_id, &pass).await {
-        Ok(_user) => {
-            Ok(next.run(req).await)
-        }
-        Err(_e) => {
-            let jar = jar
-                .remove(Cookie::build("ss_userid").path("/").build())
-                .remove(Cookie::build("ss_username").path("/").build())
-                .remove(Cookie::build("ss_password").path("/").build());
-            Ok((jar, Redirect::to("/login")).into_response())
+    match services::session::current_session(jar, &headers).await {
+        (jar, Some(session)) => {
+            // 后续处理函数通过 Extension<UserSession> 取登录用户
+            req.extensions_mut().insert(session);
+            Ok((jar, next.run(req).await).into_response())
         }
+        // 无效的会话 current_session 已清除 cookie 跨站跳转时不带 SameSite=Strict 的会话 这里不能清
+        (jar, None) => Ok((jar, Redirect::to("/login")).into_response()),
     }
 }
@@ -0,0 +1,183 @@
+use axum::body::Body;
+use axum::extract::{Form, OriginalUri, Query, State};
+use axum::Extension;
+use axum::http::{HeaderMap, Method, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use serde::Deserialize;
+use crate::{routes, services};
+use crate::handlers::search::html_resp;
+use crate::handlers::user::account::check_password;
+use crate::handlers::user::register::EMAIL_RE;
+use crate::models::user::UserSession;
+use crate::services::email::SendResult;
+use crate::services::json::ApiResponse;
+use crate::services::mail_token::TokenPurpose;
+use crate::services::user::CreateUserError;
+use crate::utils::conf::get_config;
+use crate::utils::file::file_exists;
+use crate::utils::request::trusted_base_url;
+use crate::utils::templates::render;
+use crate::utils::templates::render::TeraRenderError;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangeEmailForm {
+    email: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ConfirmQuery {
+    #[serde(default)]
+    token: String,
+}
+
+/// 开启 mail.verify 时 未验证邮箱的用户不能使用书架 放在 login_auth 之后
+pub(crate) async fn email_verified(req: Request<Body>, next: Next) -> Response {
+    if !get_config().mail.verify {
+        return next.run(req).await;
+    }
+    let Some(user_id) = req.extensions().get::<UserSession>().map(|s| s.userid) else {
+        return Redirect::to("/login").into_response();
+    };
+    match services::email::is_email_verified(user_id).await {
+        Ok(true) => next.run(req).await,
+        Ok(false) if req.method() == Method::GET && req.uri().path() == "/bookcase" => Redirect::to("/email").into_response(),
+        Ok(false) => ApiResponse::<()>::fail("请先验证邮箱", Some(vec!["验证邮箱后才能使用书架".to_string()])).into_response(),
+        Err(e) => {
+            eprintln!("is_email_verified error: {e:?}");
+            ApiResponse::<()>::fail("操作失败", Some(vec!["数据库操作失败".to_string()])).into_response()
+        }
+    }
+}
+
+/// 邮箱页面 验证邮箱和修改邮箱
+pub(crate) async fn get_email(
+    State(app_state): State<routes::app::AppState>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    Extension(session): Extension<UserSession>,
+) -> Result<impl IntoResponse, TeraRenderError> {
+    if !file_exists(format!("templates/{}/user/email.html", get_config().theme_dir)) {
+        println!("No such file or directory");
+        return Err(TeraRenderError::InvalidId);
+    }
+    let user = services::user::get_user_by_id(&session.userid.to_string()).await.map_err(|e| {
+        eprintln!("get_user_by_id error: {e:?}");
+        TeraRenderError::InvalidId
+    })?;
+    let verified = services::email::is_email_verified(user.uid).await.unwrap_or_else(|e| {
+        eprintln!("is_email_verified error: {e:?}");
+        false
+    });
+    let mut ctx = tera::Context::new();
+    services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    ctx.insert("email", &user.email);
+    ctx.insert("email_verified", &verified);
+    ctx.insert("verify_required", &get_config().mail.verify);
+    let template_path = format!("{}/user/email.html", get_config().theme_dir);
+    let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    ))
+}
+
+fn send_result(res: Result<SendResult, services::mail::MailError>, ok_msg: &str) -> ApiResponse<&'static str> {
+    match res {
+        Ok(SendResult::Sent) => ApiResponse::success(ok_msg, Some("")),
+        Ok(SendResult::TooFrequent) => ApiResponse::fail("发送失败", Some(vec!["发送太频繁，请稍后再试".to_string()])),
+        Err(e) => {
+            eprintln!("send mail error: {e:?}");
+            ApiResponse::fail("发送失败", Some(vec!["邮件发送失败，请稍后再试".to_string()]))
+        }
+    }
+}
+
+/// 重新发送验证邮件
+pub(crate) async fn post_email_send(
+    headers: HeaderMap,
+    Extension(session): Extension<UserSession>,
+) -> impl IntoResponse {
+    let user = match services::user::get_user_by_id(&session.userid.to_string()).await {
+        Ok(user) => user,
+        Err(e) => {
+            eprintln!("get_user_by_id error: {e:?}");
+            return ApiResponse::fail("发送失败", Some(vec!["用户不存在".to_string()]));
+        }
+    };
+    match services::email::is_email_verified(user.uid).await {
+        Ok(true) => return ApiResponse::fail("发送失败", Some(vec!["邮箱已经验证过了".to_string()])),
+        Ok(false) => {}
+        Err(e) => {
+            eprintln!("is_email_verified error: {e:?}");
+            return ApiResponse::fail("发送失败", Some(vec!["数据库操作失败".to_string()]));
+        }
+    }
+    let Some(base) = trusted_base_url(&headers) else {
+        return ApiResponse::fail("发送失败", Some(vec!["站点未配置 site_url，无法发送邮件".to_string()]));
+    };
+    let res = services::email::send_verify_mail(&user, &base).await;
+    send_result(res, "验证邮件已发送，请查收")
+}
+
+/// 修改邮箱 需要输入密码 确认邮件发到新邮箱
+pub(crate) async fn post_email_change(
+    headers: HeaderMap,
+    Extension(session): Extension<UserSession>,
+    Form(params): Form<ChangeEmailForm>,
+) -> impl IntoResponse {
+    let email = params.email.trim();
+    if !EMAIL_RE.is_match(email) {
+        return ApiResponse::fail("参数校验失败", Some(vec!["邮箱格式错误".to_string()]));
+    }
+    let user = match check_password(&headers, &session, &params.password).await {
+        Ok(user) => user,
+        Err(msg) => return ApiResponse::fail("修改失败", Some(vec![msg])),
+    };
+    if user.email.eq_ignore_ascii_case(email) {
+        return ApiResponse::fail("修改失败", Some(vec!["新邮箱与当前邮箱相同".to_string()]));
+    }
+    match services::email::is_email_taken(email, user.uid).await {
+        Ok(false) => {}
+        Ok(true) => return ApiResponse::fail("修改失败", Some(vec![CreateUserError::EmailAlreadyExist.to_string()])),
+        Err(e) => {
+            eprintln!("is_email_taken error: {e:?}");
+            return ApiResponse::fail("修改失败", Some(vec!["数据库操作失败".to_string()]));
+        }
+    }
+    let Some(base) = trusted_base_url(&headers) else {
+        return ApiResponse::fail("修改失败", Some(vec!["站点未配置 site_url，无法发送邮件".to_string()]));
+    };
+    let res = services::email::send_change_mail(&user, email, &base).await;
+    send_result(res, "确认邮件已发送到新邮箱，打开邮件里的链接后生效")
+}
+
+/// 邮件里的链接 验证邮箱和修改邮箱共用 不需要登录
+pub(crate) async fn get_email_confirm(Query(params): Query<ConfirmQuery>) -> Response {
+    let msg = if let Some(token) = services::mail_token::take_token(TokenPurpose::Verify, &params.token).await {
+        match services::user::get_user_by_id(&token.userid.to_string()).await {
+            Ok(user) => match services::email::confirm_verify(&token, &user).await {
+                Ok(true) => "邮箱验证成功",
+                Ok(false) => "邮箱已修改，请重新验证",
+                Err(e) => {
+                    eprintln!("confirm_verify error: {e:?}");
+                    "数据库操作失败"
+                }
+            },
+            Err(_) => "用户不存在",
+        }
+    } else if let Some(token) = services::mail_token::take_token(TokenPurpose::ChangeEmail, &params.token).await {
+        match services::email::confirm_change(&token).await {
+            Ok(()) => "邮箱修改成功",
+            Err(CreateUserError::EmailAlreadyExist) => "该邮箱已被其他用户使用",
+            Err(e) => {
+                eprintln!("confirm_change error: {e:?}");
+                "数据库操作失败"
+            }
+        }
+    } else {
+        "链接无效或已过期"
+    };
+    html_resp(format!(r#"<script>alert("{}");location.href="/email";</script>"#, msg))
+}
@@ -0,0 +1,72 @@
+use axum::extract::Form;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::models::user::{ReadLogOnNovel, UserSession};
+use crate::services;
+use crate::services::history::HistoryItem;
+use crate::services::json::ApiResponse;
+use crate::utils::conf::get_config;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SyncHistoryReq {
+    #[serde(default)]
+    items: Vec<HistoryItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DelHistoryReq {
+    articleid: u64,
+}
+
+/// 合并浏览器的阅读记录 返回合并后的完整记录 由 history.js 写回本地
+pub(crate) async fn sync_history(
+    Extension(session): Extension<UserSession>,
+    Json(params): Json<SyncHistoryReq>,
+) -> impl IntoResponse {
+    let user_id = session.userid;
+    if let Err(e) = services::history::merge_history(user_id, &params.items).await {
+        eprintln!("merge_history error: {e:?}");
+        return ApiResponse::fail("同步失败", Some(vec!["数据库操作失败".to_string()]));
+    }
+    match services::history::get_history_list(user_id).await {
+        Ok(list) => ApiResponse::success("同步成功", Some(history_items(&list))),
+        Err(e) => {
+            eprintln!("get_history_list error: {e:?}");
+            ApiResponse::fail("同步失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+/// 删除一条阅读记录 articleid 为 0 时清空
+pub(crate) async fn del_history(
+    Extension(session): Extension<UserSession>,
+    Form(params): Form<DelHistoryReq>,
+) -> impl IntoResponse {
+    let user_id = session.userid;
+    let source_id = (params.articleid > 0).then(|| get_config().source_id(params.articleid));
+    match services::history::delete_history(user_id, source_id).await {
+        Ok(_num) => ApiResponse::success("删除成功", Some("")),
+        Err(e) => {
+            eprintln!("sql error: {e:?}");
+            ApiResponse::fail("删除失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+/// 页面和接口共用的记录格式 字段与 history.js 本地记录一一对应 按阅读时间倒序
+pub(crate) fn history_items(list: &[ReadLogOnNovel]) -> Vec<Value> {
+    list.iter().map(|item| json!({
+        "articleid": item.novel.articleid,
+        "chapterid": get_config().new_id(item.log.chapterid),
+        "info_url": item.novel.info_url,
+        "read_url": item.log.read_url,
+        "articlename": item.novel.articlename,
+        "chaptername": item.log.chaptername,
+        "author": item.novel.author,
+        "img_url": item.novel.img_url,
+        "time": item.log.readtime,
+    })).collect()
+}
+
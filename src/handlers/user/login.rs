@@ -9,12 +9,17 @@ use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
 use serde::Deserialize;
 use crate::services::json::ApiResponse;
-use crate::utils::cookie::set_cookie_value;
+use crate::services::user::CreateUserError;
+use crate::utils::request::client_ip;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct LoginForm {
     pub(crate) username: String,
     pub(crate) password: String,
+    #[serde(default)]
+    pub(crate) captcha: Option<String>,
+    #[serde(default)]
+    pub(crate) captcha_id: Option<String>,
 }
 
 pub(crate) async fn get_login(
@@ -38,6 +43,7 @@ pub(crate) async fn get_login(
 
 pub(crate) async fn post_login(
     mut jar: CookieJar,
+    headers: HeaderMap,
     Form(params) : Form<LoginForm>
 ) -> impl IntoResponse {
     // 不存在这个模板 你请求你妈呢
@@ -60,15 +66,32 @@ pub(crate) async fn post_login(
     if !error_msg.is_empty() {
         return Ok((jar,ApiResponse::fail("参数校验失败", Some(error_msg))));
     }
+    if services::captcha::is_required("login")
+        && !services::captcha::verify_form(&jar, params.captcha_id.as_deref(), params.captcha.as_deref()).await
+    {
+        return Ok((jar,ApiResponse::fail("验证码错误", Some(vec!["验证码错误或已过期，请重新输入".to_string()]))));
+    }
+    let ip = client_ip(&headers);
+    if let Some(wait) = services::login_limit::check_login(&ip, &params.username).await {
+        return Ok((jar,ApiResponse::fail("登录失败", Some(vec![format!("尝试次数过多，请 {} 秒后再试", wait)]))));
+    }
     match services::user::get_user(&params.username, &params.password).await {
         Ok(user) => {
-            jar = set_cookie_value(jar, "ss_userid", user.uid.to_string().as_str(), 365 * 86400,false,false);
-            jar = set_cookie_value(jar, "ss_username", user.uname.to_owned().as_str(), 365 * 86400,false,false);
-            jar = set_cookie_value(jar, "ss_password", user.pass.to_owned().as_str(), 365 * 86400,true,false);
+            services::login_limit::record_success(&params.username).await;
+            match services::session::create_session(&user, &headers).await {
+                Ok(session) => jar = services::session::login_cookies(jar, &headers, &session),
+                Err(e) => {
+                    eprintln!("create_session error: {e:?}");
+                    return Ok((jar,ApiResponse::fail("登录失败", Some(vec!["数据库操作失败".to_string()]))));
+                }
+            }
             Ok((jar,ApiResponse::success("登录成功", Some(""))))
         },
         Err(e) => {
             eprintln!("登录失败: {:?}", e);
+            if matches!(e, CreateUserError::UserNotExist | CreateUserError::PasswordError) {
+                services::login_limit::record_failure(&ip, &params.username).await;
+            }
             Ok((jar,ApiResponse::fail("登录失败", Some(vec![e.to_string()]))))
         }
     }
@@ -1,3 +1,9 @@
 pub(crate) mod login;
 pub(crate) mod register;
 pub(crate) mod bookcase;
+pub(crate) mod vote;
+pub(crate) mod history;
+pub(crate) mod session;
+pub(crate) mod password;
+pub(crate) mod email;
+pub(crate) mod account;
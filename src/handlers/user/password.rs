@@ -0,0 +1,152 @@
+use axum::extract::{Form, OriginalUri, Query, State};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use crate::{routes, services};
+use crate::handlers::user::register::{EMAIL_RE, PASSWORD_RE};
+use crate::services::email::SendResult;
+use crate::services::json::ApiResponse;
+use crate::services::mail_token::TokenPurpose;
+use crate::utils::conf::get_config;
+use crate::utils::file::file_exists;
+use crate::utils::request::trusted_base_url;
+use crate::utils::templates::render;
+use crate::utils::templates::render::TeraRenderError;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ForgotForm {
+    email: String,
+    #[serde(default)]
+    captcha: Option<String>,
+    #[serde(default)]
+    captcha_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResetQuery {
+    #[serde(default)]
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResetForm {
+    token: String,
+    password: String,
+}
+
+/// 找回密码页面
+pub(crate) async fn get_forgot(
+    State(app_state): State<routes::app::AppState>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+) -> Result<impl IntoResponse, TeraRenderError> {
+    if !file_exists(format!("templates/{}/user/forgot.html", get_config().theme_dir)) {
+        println!("No such file or directory");
+        return Err(TeraRenderError::InvalidId);
+    }
+    let mut ctx = tera::Context::new();
+    services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    let template_path = format!("{}/user/forgot.html", get_config().theme_dir);
+    let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    ))
+}
+
+/// 发送找回密码邮件 邮箱未注册时也返回成功 不暴露哪些邮箱注册过
+pub(crate) async fn post_forgot(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Form(params): Form<ForgotForm>,
+) -> impl IntoResponse {
+    let email = params.email.trim();
+    if !EMAIL_RE.is_match(email) {
+        return ApiResponse::fail("参数校验失败", Some(vec!["邮箱格式错误".to_string()]));
+    }
+    if services::captcha::is_required("login")
+        && !services::captcha::verify_form(&jar, params.captcha_id.as_deref(), params.captcha.as_deref()).await
+    {
+        return ApiResponse::fail("验证码错误", Some(vec!["验证码错误或已过期，请重新输入".to_string()]));
+    }
+    // 邮件里的链接不能用请求带来的 Host
+    let Some(base) = trusted_base_url(&headers) else {
+        return ApiResponse::fail("发送失败", Some(vec!["站点未配置 site_url，无法发送邮件".to_string()]));
+    };
+    let user = match services::user::get_user_by_email(email).await {
+        Ok(user) => user,
+        Err(e) => {
+            eprintln!("get_user_by_email error: {e:?}");
+            return ApiResponse::fail("发送失败", Some(vec!["数据库操作失败".to_string()]));
+        }
+    };
+    if let Some(user) = user {
+        match services::email::send_reset_mail(&user, &base).await {
+            Ok(SendResult::Sent) => {}
+            Ok(SendResult::TooFrequent) => {
+                return ApiResponse::fail("发送失败", Some(vec!["发送太频繁，请稍后再试".to_string()]));
+            }
+            Err(e) => {
+                eprintln!("send_reset_mail error: {e:?}");
+                return ApiResponse::fail("发送失败", Some(vec!["邮件发送失败，请稍后再试".to_string()]));
+            }
+        }
+    }
+    ApiResponse::success("如果该邮箱已注册，找回密码的邮件已发送，请查收", Some(""))
+}
+
+/// 设置新密码页面 token 在提交时才校验
+pub(crate) async fn get_reset(
+    State(app_state): State<routes::app::AppState>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<ResetQuery>,
+) -> Result<impl IntoResponse, TeraRenderError> {
+    if !file_exists(format!("templates/{}/user/reset.html", get_config().theme_dir)) {
+        println!("No such file or directory");
+        return Err(TeraRenderError::InvalidId);
+    }
+    let mut ctx = tera::Context::new();
+    services::novel::process_tera_tag(&headers, &uri, &mut ctx);
+    ctx.insert("token", &params.token);
+    let template_path = format!("{}/user/reset.html", get_config().theme_dir);
+    let html = render::render_template(app_state.tera.clone(), &template_path, ctx).await?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    ))
+}
+
+/// 设置新密码 成功后所有设备都需要重新登录
+pub(crate) async fn post_reset(
+    jar: CookieJar,
+    Form(params): Form<ResetForm>,
+) -> impl IntoResponse {
+    if !PASSWORD_RE.is_match(&params.password) {
+        return (jar, ApiResponse::fail("参数校验失败", Some(vec!["密码必须是6-32位".to_string()])));
+    }
+    let Some(token) = services::mail_token::take_token(TokenPurpose::Reset, &params.token).await else {
+        return (jar, ApiResponse::fail("重置失败", Some(vec!["链接无效或已过期，请重新找回密码".to_string()])));
+    };
+    let user = match services::user::get_user_by_id(&token.userid.to_string()).await {
+        // 发邮件后改过邮箱 旧邮箱里的链接作废
+        Ok(user) if user.email.eq_ignore_ascii_case(&token.email) => user,
+        Ok(_) => {
+            return (jar, ApiResponse::fail("重置失败", Some(vec!["链接无效或已过期，请重新找回密码".to_string()])));
+        }
+        Err(e) => {
+            eprintln!("get_user_by_id error: {e:?}");
+            return (jar, ApiResponse::fail("重置失败", Some(vec!["用户不存在".to_string()])));
+        }
+    };
+    if let Err(e) = services::password::change_password(&user, &params.password).await {
+        eprintln!("change_password error: {e:?}");
+        return (jar, ApiResponse::fail("重置失败", Some(vec!["数据库操作失败".to_string()])));
+    }
+    if let Err(e) = services::session::delete_user_sessions(user.uid).await {
+        eprintln!("delete_user_sessions error: {e:?}");
+    }
+    services::login_limit::record_success(&user.uname).await;
+    (services::session::logout_cookies(jar), ApiResponse::success("密码已重置，请重新登录", Some("")))
+}
@@ -3,21 +3,20 @@ use axum::Form;
 use axum::http::HeaderMap;
 use axum::response::{IntoResponse, Redirect};
 use axum_extra::extract::CookieJar;
-use cookie::Cookie;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::Deserialize;
 use crate::{routes, services};
 use crate::services::json::ApiResponse;
 use crate::utils::conf::{get_config};
-use crate::utils::cookie::set_cookie_value;
 use crate::utils::file::file_exists;
+use crate::utils::request::trusted_base_url;
 use crate::utils::templates::render;
 use crate::utils::templates::render::TeraRenderError;
 
 pub(crate) static USERNAME_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-zA-Z0-9]{6,32}$").unwrap());
 pub(crate) static PASSWORD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^.{6,32}$").unwrap());
-static EMAIL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(
+pub(crate) static EMAIL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(
 r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$",
 ).unwrap());
 
@@ -26,6 +25,10 @@ pub(crate) struct RegisterForm {
     pub(crate) username: String,
     pub(crate) password: String,
     pub(crate) email: String,
+    #[serde(default)]
+    pub(crate) captcha: Option<String>,
+    #[serde(default)]
+    pub(crate) captcha_id: Option<String>,
 }
 
 pub(crate) async fn get_register(
@@ -49,6 +52,7 @@ pub(crate) async fn get_register(
 
 pub(crate) async fn post_register(
     mut jar: CookieJar,
+    headers: HeaderMap,
     Form(params) : Form<RegisterForm>
 ) -> impl IntoResponse {
     if !file_exists(format!("templates/{}/user/register.html", get_config().theme_dir)) {
@@ -76,11 +80,27 @@ pub(crate) async fn post_register(
     if !error_msg.is_empty() {
         return Ok((jar,ApiResponse::fail("参数校验失败", Some(error_msg))));
     }
+    if services::captcha::is_required("register")
+        && !services::captcha::verify_form(&jar, params.captcha_id.as_deref(), params.captcha.as_deref()).await
+    {
+        return Ok((jar,ApiResponse::fail("验证码错误", Some(vec!["验证码错误或已过期，请重新输入".to_string()]))));
+    }
     match services::user::create_user(&params.username, &params.password, &params.email).await {
         Ok(user) => {
-            jar = set_cookie_value(jar, "ss_userid", user.uid.to_string().as_str(), 365 * 86400,false,false);
-            jar = set_cookie_value(jar, "ss_username", user.uname.to_owned().as_str(), 365 * 86400,false,false);
-            jar = set_cookie_value(jar, "ss_password", user.pass.to_owned().as_str(), 365 * 86400,true,false);
+            match services::session::create_session(&user, &headers).await {
+                Ok(session) => jar = services::session::login_cookies(jar, &headers, &session),
+                Err(e) => {
+                    eprintln!("create_session error: {e:?}");
+                    return Ok((jar,ApiResponse::fail("登录失败", Some(vec!["注册成功，请重新登录".to_string()]))));
+                }
+            }
+            // 需要验证邮箱时注册后直接发验证邮件 发送失败可在邮箱页面重发
+            if get_config().mail.verify
+                && let Some(base) = trusted_base_url(&headers)
+                && let Err(e) = services::email::send_verify_mail(&user, &base).await
+            {
+                eprintln!("send_verify_mail error: {e:?}");
+            }
             Ok((jar,ApiResponse::success("注册成功", Some(""))))
         },
         Err(e) => {
@@ -90,9 +110,10 @@ pub(crate) async fn post_register(
 }
 
 pub(crate) async fn get_logout(jar: CookieJar) -> impl IntoResponse {
-    let jar = jar
-        .remove(Cookie::from("ss_userid"))
-        .remove(Cookie::from("ss_username"))
-        .remove(Cookie::from("ss_password"));
-    (jar, Redirect::to("/login"))
+    if let Some(token) = jar.get(services::session::SESSION_COOKIE).map(|c| c.value().to_string())
+        && let Err(e) = services::session::delete_session(&token).await
+    {
+        eprintln!("delete_session error: {e:?}");
+    }
+    (services::session::logout_cookies(jar), Redirect::to("/login"))
 }
\ No newline at end of file
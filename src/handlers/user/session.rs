@@ -0,0 +1,50 @@
+use axum::Extension;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use serde::Serialize;
+use crate::models::user::UserSession;
+use crate::services;
+use crate::services::json::ApiResponse;
+
+/// 会话列表中的一项 不返回完整 token
+#[derive(Debug, Serialize)]
+pub(crate) struct SessionItem {
+    id: String,
+    ip: String,
+    ua: String,
+    created: u64,
+    lastvisit: u64,
+    current: bool,
+}
+
+/// 当前用户登录中的设备
+pub(crate) async fn get_sessions(Extension(session): Extension<UserSession>) -> ApiResponse<Vec<SessionItem>> {
+    match services::session::list_sessions(session.userid).await {
+        Ok(list) => ApiResponse::success("ok", Some(list.into_iter().map(|s| SessionItem {
+            id: s.token.chars().take(8).collect(),
+            current: s.token == session.token,
+            ip: s.ip,
+            ua: s.ua,
+            created: s.created,
+            lastvisit: s.lastvisit,
+        }).collect())),
+        Err(e) => {
+            eprintln!("list_sessions error: {e:?}");
+            ApiResponse::fail("获取失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+    }
+}
+
+/// 在所有设备上退出 包括当前设备
+pub(crate) async fn post_logout_all(
+    jar: CookieJar,
+    Extension(session): Extension<UserSession>,
+) -> impl IntoResponse {
+    match services::session::delete_user_sessions(session.userid).await {
+        Ok(_num) => (services::session::logout_cookies(jar), ApiResponse::success("已在所有设备上退出", Some(""))),
+        Err(e) => {
+            eprintln!("delete_user_sessions error: {e:?}");
+            (jar, ApiResponse::fail("退出失败", Some(vec!["数据库操作失败".to_string()])))
+        }
+    }
+}
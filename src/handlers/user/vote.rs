@@ -0,0 +1,37 @@
+use axum::extract::Form;
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde::{Deserialize, Serialize};
+use crate::models::user::UserSession;
+use crate::services::json::ApiResponse;
+use crate::services::vote::{self, VoteError};
+use crate::utils::conf::get_config;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AddVoteReq {
+    articleid: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct VoteRemain {
+    remain: u32,
+}
+
+pub(crate) async fn add_vote(
+    Extension(session): Extension<UserSession>,
+    Form(params): Form<AddVoteReq>,
+) -> impl IntoResponse {
+    let user_id = session.userid;
+    if params.articleid == 0 || user_id == 0 {
+        return ApiResponse::fail("投票失败", Some(vec!["传参错误".to_string()]));
+    }
+    let source_id = get_config().source_id(params.articleid);
+    match vote::add_vote(user_id, source_id).await {
+        Ok(remain) => ApiResponse::success("投票成功", Some(VoteRemain { remain })),
+        Err(VoteError::DbErr(e)) => {
+            eprintln!("add_vote error: {e:?}");
+            ApiResponse::fail("投票失败", Some(vec!["数据库操作失败".to_string()]))
+        }
+        Err(e) => ApiResponse::fail("投票失败", Some(vec![e.to_string()])),
+    }
+}
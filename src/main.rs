@@ -8,6 +8,7 @@ mod utils;
 mod services;
 
 use std::env;
+use std::net::SocketAddr;
 use dotenv::dotenv;
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -20,6 +21,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let port = env::var("PORT")
         .expect("请在.env文件中配置PORT");
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}",port)).await?;
-    axum::serve(listener,app).await.expect("启动服务失败");
+    axum::serve(listener,app.into_make_service_with_connect_info::<SocketAddr>()).await.expect("启动服务失败");
     Ok(())
 }
\ No newline at end of file
@@ -5,7 +5,11 @@ use crate::handlers;
 pub struct Config {
     pub(crate) site_name: String,
     pub(crate) site_url: String,
+    #[serde(default)]
+    pub(crate) domains: Vec<String>,
     pub(crate) txt_url: String,
+    #[serde(default = "default_txt_encoding")]
+    pub(crate) txt_encoding: String,
     pub(crate) sys_ver: f32,
     pub(crate) root_dir: String,
     pub(crate) remote_img_url: String,
@@ -19,6 +23,8 @@ pub struct Config {
     pub(crate) index_list_num: i16,
     pub(crate) rewrite: Rewrite,
     pub(crate) sort_arr: Vec<Sort>,
+    #[serde(default = "default_rank_arr")]
+    pub(crate) rank_arr: Vec<Rank>,
     pub(crate) is_multiple: bool,
     pub(crate) confusion_value: u64,
     pub(crate) confusion_algorithm: String,
@@ -26,9 +32,25 @@ pub struct Config {
     pub(crate) link: String,
     pub(crate) is_report: bool,
     pub(crate) report_time: u32,
+    #[serde(default)]
+    pub(crate) feed_excerpt: u32,
     pub(crate) prefix: String,
     pub(crate) search: Search,
     pub(crate) cache: Cache,
+    #[serde(default)]
+    pub(crate) session: Session,
+    #[serde(default)]
+    pub(crate) jieqi_password: bool,
+    #[serde(default)]
+    pub(crate) jieqi_password_clear: bool,
+    #[serde(default)]
+    pub(crate) login_limit: LoginLimit,
+    #[serde(default)]
+    pub(crate) captcha: Captcha,
+    #[serde(default)]
+    pub(crate) mail: Mail,
+    #[serde(default)]
+    pub(crate) admin: Admin,
     pub(crate) read_page_split_mode: u8,
     pub(crate) is_lang: bool,
     pub(crate) ads: Vec<Ads>,
@@ -42,10 +64,27 @@ impl Config {
     pub(crate) fn rank_url(&self,code : &str) -> String {
         self.rewrite.rank_url.replace("{code}",code)
     }
+    pub(crate) fn rank_page_url(&self,code : &str,sort : &str,page: usize) -> String {
+        self.rewrite.rank_page_url.replace("{code}",code).replace("{sort}",sort).replace("{page}",&page.to_string())
+    }
+    /// 取排行榜配置 排序字段不在白名单内的视为不存在
+    pub(crate) fn get_rank(&self,code : &str) -> Option<&Rank> {
+        self.rank_arr.iter().find(|r| r.code == code && handlers::define::RANK_COLUMNS.contains(&r.code.as_str()))
+    }
 
     pub fn sort_url(&self,pinyin : &str,id :usize,page: usize) -> String {
         self.rewrite.sort_url.replace("{code}",pinyin).replace("{id}",&id.to_string()).replace("{page}",&page.to_string())
     }
+    pub fn search_url(&self,keyword : &str,page: usize) -> String {
+        let url = format!("{}?keyword={}",self.rewrite.search_url,urlencoding::encode(keyword));
+        if page > 1 {
+            return format!("{}&page={}",url,page);
+        }
+        url
+    }
+    pub fn complete_url(&self,sort : &str,page: usize) -> String {
+        self.rewrite.complete_url.replace("{sort}",sort).replace("{page}",&page.to_string())
+    }
     pub fn get_chapter_table(&self,id: u64) -> String {
         if self.sys_ver > 5.0 { // 如果大于等于5.0，则表示是分表章节
             return format!("{}article_chapter_{}",self.prefix,id / 10000);
@@ -120,6 +159,27 @@ impl Config {
             .replace("{page}", &page.to_string())
     }
 
+    pub fn down_url(&self, id: u64) -> String {
+        self.rewrite
+            .down_url
+            .replace("{id}", &id.to_string())
+            .replace("{sid}", &self.short_id(id).to_string())
+    }
+
+    pub fn epub_url(&self, id: u64) -> String {
+        self.rewrite
+            .epub_url
+            .replace("{id}", &id.to_string())
+            .replace("{sid}", &self.short_id(id).to_string())
+    }
+
+    pub fn novel_feed_url(&self, id: u64) -> String {
+        self.rewrite
+            .novel_feed_url
+            .replace("{id}", &id.to_string())
+            .replace("{sid}", &self.short_id(id).to_string())
+    }
+
     pub fn short_id(&self, id: u64) -> u64 {
         id / 1000
     }
@@ -175,6 +235,16 @@ pub struct Rewrite {
     pub(crate) search_url: String,
     pub(crate) lang_url: String,
     pub(crate) lang_index_url: String,
+    #[serde(default)]
+    pub(crate) down_url: String,
+    #[serde(default)]
+    pub(crate) epub_url: String,
+    #[serde(default)]
+    pub(crate) rank_page_url: String,
+    #[serde(default)]
+    pub(crate) feed_url: String,
+    #[serde(default)]
+    pub(crate) novel_feed_url: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -185,6 +255,36 @@ pub struct Sort {
     pub(crate) url: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rank {
+    pub(crate) code: String,    // 排序字段 同时作为伪静态中的{code}
+    pub(crate) caption: String,
+    #[serde(default)]
+    pub(crate) cache: u32,      // 缓存时间 0 则使用 cache.rank
+}
+
+fn default_rank_arr() -> Vec<Rank> {
+    [
+        ("allvisit", "总排行榜"),
+        ("monthvisit", "月排行榜"),
+        ("weekvisit", "周排行榜"),
+        ("dayvisit", "日排行榜"),
+        ("allvote", "总推荐榜"),
+        ("monthvote", "月推荐榜"),
+        ("weekvote", "周推荐榜"),
+        ("dayvote", "日推荐榜"),
+        ("goodnum", "收藏榜"),
+    ].iter().map(|(code, caption)| Rank {
+        code: code.to_string(),
+        caption: caption.to_string(),
+        cache: 0,
+    }).collect()
+}
+
+fn default_txt_encoding() -> String {
+    "gbk".to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Cache {
     pub(crate) home: u32,
@@ -195,6 +295,104 @@ pub struct Cache {
     pub(crate) other: u32,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Session {
+    pub(crate) days: u32,            // 登录有效天数 期间有访问自动顺延
+    pub(crate) legacy_cookie: bool,  // 接受旧版 ss_password cookie 并自动换成会话 迁移完成后关闭
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self { days: 30, legacy_cookie: true }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoginLimit {
+    pub(crate) ip_max: u32,     // 同一IP失败多少次后锁定 0 不锁定
+    pub(crate) user_max: u32,   // 同一用户名失败多少次后锁定 0 不锁定
+    pub(crate) delay: u32,      // 失败后的等待秒数 每多失败一次翻倍 0 不等待
+    pub(crate) lock_time: u32,  // 锁定秒数 也是等待时间的上限
+    pub(crate) window: u32,     // 失败次数的统计时长 超过后清零
+}
+
+impl Default for LoginLimit {
+    fn default() -> Self {
+        Self { ip_max: 20, user_max: 5, delay: 1, lock_time: 900, window: 3600 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Captcha {
+    pub(crate) register: bool,  // 注册需要验证码
+    pub(crate) login: bool,     // 登录需要验证码
+    pub(crate) search: u32,     // 同一IP一小时内搜索超过多少次后需要验证码 0 不需要
+    pub(crate) length: u32,     // 验证码字符数
+    pub(crate) ttl: u32,        // 验证码有效秒数
+}
+
+impl Default for Captcha {
+    fn default() -> Self {
+        Self { register: false, login: false, search: 0, length: 4, ttl: 300 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Mail {
+    pub(crate) driver: String,      // smtp 发送 file 保存到 dir 目录 log 只打印到控制台
+    pub(crate) host: String,        // SMTP 服务器
+    pub(crate) port: u16,           // SMTP 端口
+    pub(crate) security: String,    // tls(465) starttls(587) none(25)
+    pub(crate) username: String,    // SMTP 用户名 为空时不认证
+    pub(crate) password: String,    // SMTP 密码或授权码
+    pub(crate) from: String,        // 发件地址
+    pub(crate) from_name: String,   // 发件人名称 为空时使用 site_name
+    pub(crate) dir: String,         // file 驱动的保存目录
+    pub(crate) verify: bool,        // 使用书架前必须验证邮箱
+    pub(crate) token_ttl: u32,      // 邮件链接有效秒数
+}
+
+impl Default for Mail {
+    fn default() -> Self {
+        Self {
+            driver: "log".to_string(),
+            host: String::new(),
+            port: 465,
+            security: "tls".to_string(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            from_name: String::new(),
+            dir: "mail".to_string(),
+            verify: false,
+            token_ttl: 3600,
+        }
+    }
+}
+
+/// 后台权限 填杰奇 system_users.groupid 2 为系统管理员
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Admin {
+    pub(crate) config: Vec<u32>,    // 可以修改站点配置的用户组
+    pub(crate) content: Vec<u32>,   // 可以管理小说和章节、处理章节报错、查看搜索词的用户组
+    pub(crate) users: Vec<u32>,     // 可以管理登录锁定的用户组
+}
+
+impl Default for Admin {
+    fn default() -> Self {
+        Self {
+            config: vec![2],
+            content: vec![2],
+            users: vec![2],
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Search {
     pub(crate) limit: u16,      // 搜索结果数
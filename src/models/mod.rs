@@ -1,3 +1,4 @@
 pub(crate) mod novel;
 pub(crate) mod config;
-pub(crate) mod user;
\ No newline at end of file
+pub(crate) mod user;
+pub(crate) mod report;
\ No newline at end of file
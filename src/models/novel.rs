@@ -97,3 +97,34 @@ pub(crate) struct LangTail {
     #[sea_orm(skip)]
     pub index_url: String,
 }
+
+/// 后台小说管理 articleid 为数据库中的原始ID 不过滤隐藏的小说
+#[derive(Debug, FromQueryResult, Serialize, Deserialize)]
+pub(crate) struct NovelAdmin {
+    pub(crate) articleid: u64,
+    pub(crate) articlename: String,
+    pub(crate) author: String,
+    pub(crate) intro: String,
+    pub(crate) keywords: String,
+    pub(crate) sortid: u8,
+    pub(crate) fullflag: bool,
+    pub(crate) display: bool,
+    pub(crate) lastupdate: u64,
+    pub(crate) lastchapter: String,
+    pub(crate) lastchapterid: u64,
+    #[sea_orm(skip)]
+    pub(crate) info_url: String,
+}
+
+/// 后台章节管理 chapterid 为数据库中的原始ID
+#[derive(Debug, FromQueryResult, Serialize, Deserialize)]
+pub(crate) struct ChapterAdmin {
+    pub(crate) chapterid: u64,
+    pub(crate) chaptername: String,
+    pub(crate) chaptertype: u8,
+    pub(crate) chapterorder: u32,
+    pub(crate) words: u64,
+    pub(crate) lastupdate: u64,
+    #[sea_orm(skip)]
+    pub(crate) read_url: String,
+}
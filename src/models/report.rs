@@ -0,0 +1,26 @@
+use sea_orm::FromQueryResult;
+use serde::{Deserialize, Serialize};
+
+/// 待处理
+pub(crate) const REPORT_PENDING: u8 = 0;
+/// 已处理
+pub(crate) const REPORT_RESOLVED: u8 = 1;
+/// 已忽略
+pub(crate) const REPORT_DISMISSED: u8 = 2;
+
+/// 章节报错 articleid 和 chapterid 都是数据库中的原始ID
+#[derive(Debug, FromQueryResult, Serialize, Deserialize)]
+pub(crate) struct ChapterReport {
+    pub(crate) reportid: u64,
+    pub(crate) articleid: u64,
+    pub(crate) articlename: String,
+    pub(crate) chapterid: u64,
+    pub(crate) chaptername: String,
+    pub(crate) reason: String,
+    pub(crate) fingerprint: String,
+    pub(crate) host: String,
+    pub(crate) reporttime: u64,
+    pub(crate) status: u8,
+    #[sea_orm(skip)]
+    pub(crate) read_url: String,
+}
@@ -31,4 +31,42 @@ pub(crate) struct BookShelf {
 pub(crate) struct BookShelfOnNovel{
     pub(crate) case: BookShelf,
     pub(crate) novel: Novel,
-}
\ No newline at end of file
+    /// 书签之后有新章节
+    pub(crate) has_update: bool,
+    /// 书签之后的章节数 不含分卷
+    pub(crate) unread: u64,
+    /// 最近阅读时间 取自阅读记录 没有记录为 0
+    pub(crate) readtime: u64,
+}
+
+/// 阅读记录 ID 均为数据库中的原始ID
+#[derive(Debug, FromQueryResult,Serialize,Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct ReadLog {
+    pub(crate) articleid: u64,
+    pub(crate) chapterid: u64,
+    pub(crate) chaptername: String,
+    pub(crate) readtime: u64,
+    #[sea_orm(skip)]
+    pub(crate) read_url: String,
+}
+
+#[derive(Debug, Serialize,Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct ReadLogOnNovel{
+    pub(crate) log: ReadLog,
+    pub(crate) novel: Novel,
+}
+
+/// 登录会话 token 只保存在 httponly cookie 里
+#[derive(Debug, Clone, FromQueryResult, Serialize, Deserialize)]
+pub(crate) struct UserSession {
+    pub(crate) token: String,
+    pub(crate) userid: u64,
+    pub(crate) uname: String,
+    pub(crate) ip: String,
+    pub(crate) ua: String,
+    pub(crate) created: u64,
+    pub(crate) lastvisit: u64,
+    pub(crate) expire: u64,
+}
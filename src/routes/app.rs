@@ -7,19 +7,43 @@ use tower_http::compression::CompressionLayer;
 use tower_http::services::ServeDir;
 use crate::handlers::author::get_author;
 use crate::handlers::chapter::get_chapter;
+use crate::handlers::complete::get_complete;
+use crate::handlers::down::{get_down, get_epub};
 use crate::handlers::history::get_history;
 use crate::handlers::index::{get_index};
 use crate::handlers::index_list::{get_index_list_page, get_lang_index_list_page};
 use crate::handlers::info::{ get_info_3in1, get_lang_info_3in1};
 use crate::handlers::rank::{get_rank, get_top};
 use crate::handlers::search::{get_search, post_search};
+use crate::handlers::captcha::get_captcha;
 use crate::handlers::sort::get_sort;
-use crate::handlers::user::bookcase::{add_bookcase, del_bookcase, get_bookcase, login_auth};
+use crate::handlers::user::bookcase::{add_bookcase, del_bookcase, get_bookcase, get_bookcase_updates, login_auth};
+use crate::handlers::user::history::{del_history, sync_history};
+use crate::handlers::user::session::{get_sessions, post_logout_all};
+use crate::handlers::user::password::{get_forgot, get_reset, post_forgot, post_reset};
+use crate::handlers::user::account::{get_account, get_account_export, post_account_delete, post_account_password};
+use crate::handlers::user::email::{email_verified, get_email, get_email_confirm, post_email_change, post_email_send};
 use crate::handlers::user::login::{get_login, post_login};
 use crate::handlers::user::register::{get_logout, get_register, post_register};
+use crate::handlers::user::vote::add_vote;
+use crate::services;
 use crate::utils::conf::{get_config};
 use crate::utils::db::conn::{init_conn, DB_CONN};
-use crate::handlers::admin::index::{admin_conf_edit, admin_conf_get, index};
+use crate::utils::db::schema::init_schema;
+use crate::handlers::admin::index::{admin_auth, admin_conf_edit, admin_conf_get, admin_login, admin_logout, index};
+use crate::services::admin::AdminRole;
+use crate::handlers::admin::search::admin_search_list;
+use crate::handlers::admin::chapter::{admin_chapter_delete, admin_chapter_list, admin_chapter_order, admin_chapter_rename, admin_chapter_save, admin_chapter_text};
+use crate::handlers::admin::novel::{admin_novel_display, admin_novel_edit, admin_novel_get, admin_novel_list, admin_novel_recount};
+use crate::handlers::admin::login::{admin_login_clear, admin_login_list};
+use crate::handlers::admin::report::{admin_report_dismiss, admin_report_list, admin_report_resolve};
+use crate::handlers::report::post_report;
+use crate::handlers::sitemap::{get_sitemap_page, get_sitemap_xml};
+use crate::handlers::feed::{get_feed, get_novel_feed_xml};
+use crate::handlers::api::{api_chapter, api_chapters, api_novel, api_rank, api_ranks, api_sort, api_sorts};
+use crate::handlers::opds::{opds_complete, opds_latest, opds_opensearch, opds_root, opds_search, opds_sort};
+use crate::handlers::legado::{get_legado_source, legado_book_info, legado_chapter, legado_search, legado_toc};
+use crate::utils::request::real_ip;
 use crate::utils::templates::init::init_tera;
 #[derive(Clone)]
 #[allow(dead_code)]
@@ -30,6 +54,11 @@ pub struct AppState {
 pub async fn router() -> Router {
     let db = init_conn().await.expect("初始化数据库连接失败");
     DB_CONN.set(db).expect("DB_CONN 已经初始化过了");
+    if let Err(e) = init_schema().await {
+        eprintln!("创建数据表失败：{e:?}");
+    }
+    tokio::spawn(services::search::record_task());
+    tokio::spawn(services::visit::visit_task());
     let tera = init_tera().unwrap();
     let template_names: Vec<&str> = tera.get_template_names().collect();
     eprintln!("已加载模板：{:?}", template_names);
@@ -39,6 +68,24 @@ pub async fn router() -> Router {
         router = router.route(trim_suffix(get_config().rewrite.lang_url.as_str()), get(get_lang_info_3in1))
             .route(trim_suffix(get_config().rewrite.lang_index_url.as_str()), get(get_lang_index_list_page));
     }
+    if !get_config().rewrite.down_url.is_empty() {
+        router = router.route(trim_suffix(get_config().rewrite.down_url.as_str()), get(get_down));
+    }
+    if !get_config().rewrite.complete_url.is_empty() {
+        router = router.route(trim_suffix(get_config().rewrite.complete_url.as_str()), get(get_complete));
+    }
+    if !get_config().rewrite.rank_page_url.is_empty() {
+        router = router.route(trim_suffix(get_config().rewrite.rank_page_url.as_str()), get(get_rank));
+    }
+    if !get_config().rewrite.epub_url.is_empty() {
+        router = router.route(trim_suffix(get_config().rewrite.epub_url.as_str()), get(get_epub));
+    }
+    if !get_config().rewrite.feed_url.is_empty() {
+        router = router.route(get_config().rewrite.feed_url.as_str(), get(get_feed));
+    }
+    if !get_config().rewrite.novel_feed_url.is_empty() {
+        router = router.route(trim_suffix(get_config().rewrite.novel_feed_url.as_str()), get(get_novel_feed_xml));
+    }
     router.route("/", get(get_index))
         .route(trim_suffix(get_config().rewrite.info_url.as_str()), get(get_info_3in1))
         .route(trim_suffix(get_config().rewrite.index_list_url.as_str()), get(get_index_list_page))
@@ -51,15 +98,72 @@ pub async fn router() -> Router {
         .route(get_config().rewrite.search_url.as_str(), get(get_search).post(post_search))
         .route("/login", get(get_login).post(post_login))
         .route("/register", get(get_register).post(post_register))
-        .route("/bookcase", get(get_bookcase).layer(middleware::from_fn(login_auth)))
-        .route("/delbookcase", post(del_bookcase).layer(middleware::from_fn(login_auth)))
-        .route("/addbookcase", post(add_bookcase).layer(middleware::from_fn(login_auth)))
+        .route("/captcha", get(get_captcha))
+        .route("/password/forgot", get(get_forgot).post(post_forgot))
+        .route("/password/reset", get(get_reset).post(post_reset))
+        .route("/email", get(get_email).layer(middleware::from_fn(login_auth)))
+        .route("/email/send", post(post_email_send).layer(middleware::from_fn(login_auth)))
+        .route("/email/change", post(post_email_change).layer(middleware::from_fn(login_auth)))
+        .route("/email/confirm", get(get_email_confirm))
+        .route("/account", get(get_account).layer(middleware::from_fn(login_auth)))
+        .route("/account/password", post(post_account_password).layer(middleware::from_fn(login_auth)))
+        .route("/account/delete", post(post_account_delete).layer(middleware::from_fn(login_auth)))
+        .route("/account/export", get(get_account_export).layer(middleware::from_fn(login_auth)))
+        .route("/bookcase", get(get_bookcase).layer(middleware::from_fn(email_verified)).layer(middleware::from_fn(login_auth)))
+        .route("/bookcase/updates", get(get_bookcase_updates).layer(middleware::from_fn(email_verified)).layer(middleware::from_fn(login_auth)))
+        .route("/delbookcase", post(del_bookcase).layer(middleware::from_fn(email_verified)).layer(middleware::from_fn(login_auth)))
+        .route("/addbookcase", post(add_bookcase).layer(middleware::from_fn(email_verified)).layer(middleware::from_fn(login_auth)))
+        .route("/history/sync", post(sync_history).layer(middleware::from_fn(login_auth)))
+        .route("/history/del", post(del_history).layer(middleware::from_fn(login_auth)))
+        .route("/addvote", post(add_vote).layer(middleware::from_fn(login_auth)))
         .route("/logout", get(get_logout))
-        .route(&admin_url, get(index))
-        .route(format!("{}/get",admin_url).as_str(), post(admin_conf_get))
-        .route(format!("{}/edit",admin_url).as_str(), post(admin_conf_edit))
+        .route("/logout/all", post(post_logout_all).layer(middleware::from_fn(login_auth)))
+        .route("/sessions", get(get_sessions).layer(middleware::from_fn(login_auth)))
+        .route("/report", post(post_report))
+        .route("/sitemap.xml", get(get_sitemap_xml))
+        .route("/sitemap/{kind}/{page}", get(get_sitemap_page))
+        .route("/legado/source.json", get(get_legado_source))
+        .route("/legado/search", get(legado_search))
+        .route("/legado/book/{id}", get(legado_book_info))
+        .route("/legado/toc/{id}", get(legado_toc))
+        .route("/legado/chapter/{id}/{cid}", get(legado_chapter))
+        .route("/opds", get(opds_root))
+        .route("/opds/opensearch.xml", get(opds_opensearch))
+        .route("/opds/search", get(opds_search))
+        .route("/opds/latest", get(opds_latest))
+        .route("/opds/complete", get(opds_complete))
+        .route("/opds/sort/{code}", get(opds_sort))
+        .route("/api/novel/{id}", get(api_novel))
+        .route("/api/novel/{id}/chapters", get(api_chapters))
+        .route("/api/novel/{id}/chapter/{cid}", get(api_chapter))
+        .route("/api/sorts", get(api_sorts))
+        .route("/api/sort/{code}", get(api_sort))
+        .route("/api/ranks", get(api_ranks))
+        .route("/api/rank/{code}", get(api_rank))
+        .route(&admin_url, get(index).post(admin_login))
+        .route(format!("{}/logout",admin_url).as_str(), post(admin_logout))
+        .route(format!("{}/get",admin_url).as_str(), post(admin_conf_get).layer(middleware::from_fn_with_state(AdminRole::Config, admin_auth)))
+        .route(format!("{}/edit",admin_url).as_str(), post(admin_conf_edit).layer(middleware::from_fn_with_state(AdminRole::Config, admin_auth)))
+        .route(format!("{}/report/list",admin_url).as_str(), post(admin_report_list).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/report/resolve",admin_url).as_str(), post(admin_report_resolve).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/report/dismiss",admin_url).as_str(), post(admin_report_dismiss).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/login/list",admin_url).as_str(), post(admin_login_list).layer(middleware::from_fn_with_state(AdminRole::Users, admin_auth)))
+        .route(format!("{}/login/clear",admin_url).as_str(), post(admin_login_clear).layer(middleware::from_fn_with_state(AdminRole::Users, admin_auth)))
+        .route(format!("{}/search/list",admin_url).as_str(), post(admin_search_list).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/novel/list",admin_url).as_str(), post(admin_novel_list).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/novel/get",admin_url).as_str(), post(admin_novel_get).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/novel/edit",admin_url).as_str(), post(admin_novel_edit).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/novel/display",admin_url).as_str(), post(admin_novel_display).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/novel/recount",admin_url).as_str(), post(admin_novel_recount).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/list",admin_url).as_str(), post(admin_chapter_list).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/rename",admin_url).as_str(), post(admin_chapter_rename).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/order",admin_url).as_str(), post(admin_chapter_order).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/delete",admin_url).as_str(), post(admin_chapter_delete).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/text",admin_url).as_str(), post(admin_chapter_text).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
+        .route(format!("{}/chapter/save",admin_url).as_str(), post(admin_chapter_save).layer(middleware::from_fn_with_state(AdminRole::Content, admin_auth)))
         .nest_service("/static", ServeDir::new("public"))
         .layer(CompressionLayer::new())
+        .layer(middleware::from_fn(real_ip))
         .with_state(AppState { tera})
 }
 
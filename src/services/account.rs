@@ -0,0 +1,53 @@
+use sea_orm::{DbErr, Values};
+use serde::Serialize;
+use crate::models::user::{BookShelf, User};
+use crate::services::password::UNUSABLE_PASS;
+use crate::services::session::delete_user_sessions;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_all_as};
+
+/// 导出的书架 ID 为混淆后的ID
+#[derive(Debug, Serialize)]
+pub(crate) struct BookcaseExport {
+    articleid: u64,
+    articlename: String,
+    chapterid: u64,
+    chaptername: String,
+}
+
+/// 书架原始记录 不读取小说信息 小说已删除的也导出
+pub(crate) async fn get_bookcase_export(user_id: u64) -> Result<Vec<BookcaseExport>, DbErr> {
+    let sql = format!("SELECT * FROM {}article_bookcase WHERE userid = ? ORDER BY caseid", get_config().prefix);
+    let rows = query_all_as::<BookShelf>(&sql, Some(Values(vec![user_id.into()]))).await?;
+    Ok(rows
+        .into_iter()
+        .map(|b| BookcaseExport {
+            articleid: get_config().new_id(b.articleid),
+            articlename: b.articlename,
+            chapterid: if b.chapterid == 0 { 0 } else { get_config().new_id(b.chapterid) },
+            chaptername: b.chaptername,
+        })
+        .collect())
+}
+
+/// 注销账号 删除书架、阅读记录、会话等数据 system_users 保留 uid 但抹掉用户名、邮箱和密码
+///
+/// 用户名改为 deleted_{uid} 原用户名可以重新注册
+pub(crate) async fn delete_account(user: &User) -> Result<(), DbErr> {
+    let prefix = &get_config().prefix;
+    for table in ["article_bookcase", "article_readlog", "article_uservote", "user_password", "user_email"] {
+        exec_sql(
+            format!("DELETE FROM {}{} WHERE userid = ?", prefix, table).as_str(),
+            Some(Values(vec![user.uid.into()])),
+        ).await?;
+    }
+    let uname = format!("deleted_{}", user.uid);
+    let sql = if get_config().sys_ver < 2.0 {
+        format!("UPDATE {}system_users SET uname = ?, name = ?, email = '', pass = ? WHERE uid = ?", prefix)
+    } else {
+        format!("UPDATE {}system_users SET uname = ?, name = ?, email = '', pass = ?, salt = '' WHERE uid = ?", prefix)
+    };
+    exec_sql(&sql, Some(Values(vec![uname.as_str().into(), "已注销".into(), UNUSABLE_PASS.into(), user.uid.into()]))).await?;
+    delete_user_sessions(user.uid).await?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use axum_extra::extract::CookieJar;
+use hmac::{Hmac, Mac};
+use sea_orm::{DbErr, Values};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::services::session::{get_session, SESSION_COOKIE};
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_count};
+
+/// 用 ADMIN_TOKEN 进入后台后保存凭证的 cookie
+pub(crate) const ADMIN_COOKIE: &str = "ss_admin";
+/// ADMIN_TOKEN 凭证的有效秒数
+pub(crate) const ADMIN_COOKIE_TTL: u64 = 7200;
+/// ADMIN_TOKEN 输错时计入登录失败的名字 含 # 不会和用户名冲突
+pub(crate) const BREAK_GLASS_NAME: &str = "#admin_token";
+
+/// 后台权限 对应 conf.json 的 admin 里配置的用户组
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AdminRole {
+    /// 站点配置
+    Config,
+    /// 小说、章节报错、搜索词等内容管理
+    Content,
+    /// 登录锁定等用户管理
+    Users,
+}
+
+impl AdminRole {
+    const ALL: [AdminRole; 3] = [AdminRole::Config, AdminRole::Content, AdminRole::Users];
+
+    fn groups(self) -> Vec<u32> {
+        let conf = &get_config().admin;
+        match self {
+            AdminRole::Config => conf.config.clone(),
+            AdminRole::Content => conf.content.clone(),
+            AdminRole::Users => conf.users.clone(),
+        }
+    }
+}
+
+/// 当前后台操作者 用 ADMIN_TOKEN 进入的 uid 为 0 拥有全部权限
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AdminUser {
+    pub(crate) uid: u64,
+    pub(crate) uname: String,
+    pub(crate) roles: Vec<AdminRole>,
+}
+
+/// 可用的 ADMIN_TOKEN 非调试模式下默认值不可用
+fn break_glass_token() -> Option<String> {
+    let token = std::env::var("ADMIN_TOKEN").unwrap_or_default();
+    if token.is_empty() || (!cfg!(debug_assertions) && token == "admin_token") {
+        return None;
+    }
+    Some(token)
+}
+
+/// 是否可以用 ADMIN_TOKEN 进入后台
+pub(crate) fn break_glass_enabled() -> bool {
+    break_glass_token().is_some()
+}
+
+/// 校验输入的 ADMIN_TOKEN 比较摘要 不按字符提前返回
+pub(crate) fn verify_break_glass(input: &str) -> bool {
+    break_glass_token().is_some_and(|token| Sha256::digest(input.as_bytes()) == Sha256::digest(token.as_bytes()))
+}
+
+fn sign(token: &str, expire: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC 可以使用任意长度的密钥");
+    mac.update(format!("admin|{}", expire).as_bytes());
+    mac
+}
+
+/// ADMIN_TOKEN 校验通过后写入 cookie 的值 格式为 过期时间.签名 修改 ADMIN_TOKEN 后全部失效
+pub(crate) fn break_glass_cookie() -> Option<String> {
+    let token = break_glass_token()?;
+    let expire = timestamp_10() as u64 + ADMIN_COOKIE_TTL;
+    Some(format!("{}.{}", expire, hex::encode(sign(&token, expire).finalize().into_bytes())))
+}
+
+fn verify_break_glass_cookie(value: &str) -> bool {
+    let Some(token) = break_glass_token() else {
+        return false;
+    };
+    let Some((expire, sig)) = value.split_once('.') else {
+        return false;
+    };
+    let (Ok(expire), Ok(sig)) = (expire.parse::<u64>(), hex::decode(sig)) else {
+        return false;
+    };
+    expire > timestamp_10() as u64 && sign(&token, expire).verify_slice(&sig).is_ok()
+}
+
+/// 用户所在的用户组 用户不存在时为 0
+async fn get_groupid(user_id: u64) -> Result<u64, DbErr> {
+    let sql = format!("SELECT CAST(groupid AS SIGNED) AS cnt FROM {}system_users WHERE uid = ? LIMIT 1", get_config().prefix);
+    query_count(&sql, Some(Values(vec![user_id.into()]))).await
+}
+
+/// 取当前后台操作者 每次都查用户组 调整用户组后立即生效
+pub(crate) async fn current_admin(jar: &CookieJar) -> Option<AdminUser> {
+    if let Some(cookie) = jar.get(ADMIN_COOKIE)
+        && verify_break_glass_cookie(cookie.value())
+    {
+        return Some(AdminUser {
+            uid: 0,
+            uname: "ADMIN_TOKEN".to_string(),
+            roles: AdminRole::ALL.to_vec(),
+        });
+    }
+    let session = get_session(jar.get(SESSION_COOKIE)?.value()).await?;
+    let groupid = get_groupid(session.userid)
+        .await
+        .inspect_err(|e| eprintln!("get_groupid error: {e:?}"))
+        .ok()?;
+    let roles: Vec<AdminRole> = AdminRole::ALL
+        .into_iter()
+        .filter(|role| groupid > 0 && role.groups().contains(&(groupid as u32)))
+        .collect();
+    if roles.is_empty() {
+        return None;
+    }
+    Some(AdminUser {
+        uid: session.userid,
+        uname: session.uname,
+        roles,
+    })
+}
+
+/// 记录后台操作到 {prefix}admin_log 写入失败时打印到标准错误 不影响操作结果
+pub(crate) async fn admin_log(admin: &AdminUser, ip: &str, action: &str, articleid: u64, detail: &str) {
+    let detail: String = detail.chars().take(255).collect();
+    let sql = format!(
+        "INSERT INTO {}admin_log (uid, uname, action, articleid, detail, ip, logtime) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        get_config().prefix
+    );
+    let values = Values(vec![
+        admin.uid.into(),
+        admin.uname.as_str().into(),
+        action.into(),
+        articleid.into(),
+        detail.as_str().into(),
+        ip.chars().take(64).collect::<String>().into(),
+        timestamp_10().into(),
+    ]);
+    if let Err(e) = exec_sql(&sql, Some(values)).await {
+        eprintln!("admin_log error: {e:?} 后台 {}({}) {} {} {} ip={}", admin.uname, admin.uid, action, articleid, detail, ip);
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::Path;
+use encoding_rs::Encoding;
+use sea_orm::{DbErr, Value, Values};
+use serde::Serialize;
+use thiserror::Error;
+use crate::models::novel::ChapterAdmin;
+use crate::services::admin_novel::recount_novel;
+use crate::services::novel::{chapter_txt_url, txt_encoding};
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_all_as};
+
+/// 每条 UPDATE 语句最多调整的章节数
+const ORDER_BATCH: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum ChapterEditError {
+    #[error("数据库操作失败")]
+    Db(#[from] DbErr),
+    #[error("读写文件失败：{0}")]
+    Io(#[from] std::io::Error),
+    #[error("章节不存在")]
+    NotFound,
+    #[error("txt_url 是远程地址 不能在后台编辑")]
+    Remote,
+    #[error("章节顺序与章节列表不一致 请刷新后重试")]
+    OrderMismatch,
+    #[error("不支持保存为 {0} 编码")]
+    Unsupported(&'static str),
+    #[error("这些字符不能保存为 {0}：{1}")]
+    Unmappable(&'static str, String),
+}
+
+/// 后台编辑的章节正文
+#[derive(Debug, Serialize)]
+pub(crate) struct ChapterText {
+    chapterid: u64,
+    chaptername: String,
+    /// 读取和保存时使用的编码
+    encoding: &'static str,
+    content: String,
+}
+
+fn words_column() -> &'static str {
+    // 杰奇1.7 字数字段为 size
+    if get_config().sys_ver < 2.0 { "size" } else { "words" }
+}
+
+/// 小说的全部章节 按前台的顺序 包括分卷
+pub(crate) async fn get_chapters(source_id: u64) -> Result<Vec<ChapterAdmin>, DbErr> {
+    let sql = format!(
+        "SELECT chapterid, chaptername, chaptertype, chapterorder, CAST({} AS UNSIGNED) AS words, lastupdate FROM {} WHERE articleid = ? ORDER BY chapterorder ASC, chapterid ASC",
+        words_column(),
+        get_config().get_chapter_table(source_id)
+    );
+    let mut rows: Vec<ChapterAdmin> = query_all_as(&sql, Some(Values(vec![source_id.into()]))).await?;
+    for row in rows.iter_mut() {
+        row.read_url = get_config().read_url(get_config().new_id(source_id), get_config().new_id(row.chapterid), 1);
+    }
+    Ok(rows)
+}
+
+async fn get_chapter(source_id: u64, chapter_id: u64) -> Result<ChapterAdmin, ChapterEditError> {
+    get_chapters(source_id)
+        .await?
+        .into_iter()
+        .find(|c| c.chapterid == chapter_id)
+        .ok_or(ChapterEditError::NotFound)
+}
+
+/// 修改章节名 最新章节随之更新
+pub(crate) async fn rename_chapter(source_id: u64, chapter_id: u64, name: &str) -> Result<(), ChapterEditError> {
+    let sql = format!("UPDATE {} SET chaptername = ? WHERE articleid = ? AND chapterid = ?", get_config().get_chapter_table(source_id));
+    get_chapter(source_id, chapter_id).await?;
+    exec_sql(&sql, Some(Values(vec![name.into(), source_id.into(), chapter_id.into()]))).await?;
+    recount_novel(source_id).await?;
+    Ok(())
+}
+
+/// 按传入的顺序重排 必须包含这本小说的全部章节 chapterorder 从1开始
+pub(crate) async fn reorder_chapters(source_id: u64, ids: &[u64]) -> Result<(), ChapterEditError> {
+    let mut current: Vec<u64> = get_chapters(source_id).await?.into_iter().map(|c| c.chapterid).collect();
+    let mut sorted = ids.to_vec();
+    current.sort_unstable();
+    sorted.sort_unstable();
+    if current != sorted {
+        return Err(ChapterEditError::OrderMismatch);
+    }
+    let table = get_config().get_chapter_table(source_id);
+    for (batch_index, batch) in ids.chunks(ORDER_BATCH).enumerate() {
+        let mut values: Vec<Value> = Vec::with_capacity(batch.len() * 3 + 1);
+        for (i, id) in batch.iter().enumerate() {
+            values.push((*id).into());
+            values.push(((batch_index * ORDER_BATCH + i + 1) as u64).into());
+        }
+        values.push(source_id.into());
+        values.extend(batch.iter().map(|id| Value::from(*id)));
+        let sql = format!(
+            "UPDATE {} SET chapterorder = CASE chapterid {} END WHERE articleid = ? AND chapterid IN ({})",
+            table,
+            vec!["WHEN ? THEN ?"; batch.len()].join(" "),
+            vec!["?"; batch.len()].join(", ")
+        );
+        exec_sql(&sql, Some(Values(values))).await?;
+    }
+    recount_novel(source_id).await?;
+    Ok(())
+}
+
+/// 删除章节和对应的txt 返回删除的条数
+pub(crate) async fn delete_chapters(source_id: u64, ids: &[u64]) -> Result<u64, ChapterEditError> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let sql = format!(
+        "DELETE FROM {} WHERE articleid = ? AND chapterid IN ({})",
+        get_config().get_chapter_table(source_id),
+        vec!["?"; ids.len()].join(", ")
+    );
+    let mut values: Vec<Value> = vec![source_id.into()];
+    values.extend(ids.iter().map(|id| Value::from(*id)));
+    let n = exec_sql(&sql, Some(Values(values))).await?;
+    if !is_remote_txt() {
+        for id in ids {
+            let _ = tokio::fs::remove_file(chapter_txt_url(source_id, *id)).await;
+        }
+    }
+    recount_novel(source_id).await?;
+    Ok(n)
+}
+
+fn is_remote_txt() -> bool {
+    let txt_url = get_config().txt_url.trim().to_ascii_lowercase();
+    txt_url.starts_with("http://") || txt_url.starts_with("https://")
+}
+
+/// 读取章节正文 不替换 <br> 原样编辑
+pub(crate) async fn get_chapter_text(source_id: u64, chapter_id: u64) -> Result<ChapterText, ChapterEditError> {
+    if is_remote_txt() {
+        return Err(ChapterEditError::Remote);
+    }
+    let chapter = get_chapter(source_id, chapter_id).await?;
+    let path = chapter_txt_url(source_id, chapter_id);
+    let bytes = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        // 还没有正文的章节可以直接新建
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    let encoding = txt_encoding(&bytes);
+    // decode 会去掉 BOM
+    let (content, _, _) = encoding.decode(&bytes);
+    Ok(ChapterText {
+        chapterid: chapter.chapterid,
+        chaptername: chapter.chaptername,
+        encoding: encoding.name(),
+        content: content.into_owned(),
+    })
+}
+
+/// 保存章节正文 按原文件的编码、BOM 和换行写回 同时更新章节字数
+pub(crate) async fn save_chapter_text(source_id: u64, chapter_id: u64, content: &str) -> Result<(), ChapterEditError> {
+    if is_remote_txt() {
+        return Err(ChapterEditError::Remote);
+    }
+    get_chapter(source_id, chapter_id).await?;
+    let path = chapter_txt_url(source_id, chapter_id);
+    let old = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    let encoding = txt_encoding(&old);
+    // UTF-16 没有编码器 encode 会输出 UTF-8
+    if encoding.output_encoding() != encoding {
+        return Err(ChapterEditError::Unsupported(encoding.name()));
+    }
+    let mut text = content.replace("\r\n", "\n");
+    if old.windows(2).any(|w| w == b"\r\n") {
+        text = text.replace('\n', "\r\n");
+    }
+    let (encoded, _, had_errors) = encoding.encode(&text);
+    if had_errors {
+        let mut unmappable: Vec<char> = vec![];
+        for c in text.chars() {
+            if !unmappable.contains(&c) && encoding.encode(c.encode_utf8(&mut [0u8; 4])).2 {
+                unmappable.push(c);
+            }
+        }
+        return Err(ChapterEditError::Unmappable(encoding.name(), unmappable.into_iter().collect()));
+    }
+    let mut bytes = match Encoding::for_bom(&old) {
+        Some((_, bom_len)) => old[..bom_len].to_vec(),
+        None => vec![],
+    };
+    bytes.extend_from_slice(&encoded);
+    if let Some(dir) = Path::new(&path).parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    // 先写临时文件再改名 写到一半时前台不会读到残缺的正文
+    let tmp = format!("{}.tmp", path);
+    tokio::fs::write(&tmp, &bytes).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    let words = text.chars().filter(|c| !c.is_whitespace()).count() as u64;
+    let sql = format!(
+        "UPDATE {} SET {} = ? WHERE articleid = ? AND chapterid = ?",
+        get_config().get_chapter_table(source_id),
+        words_column()
+    );
+    exec_sql(&sql, Some(Values(vec![words.into(), source_id.into(), chapter_id.into()]))).await?;
+    recount_novel(source_id).await?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use sea_orm::{DbErr, Value, Values};
+use serde::Serialize;
+use crate::models::novel::NovelAdmin;
+use crate::services::novel::purge_novel_cache_all;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, get_one_as, query_all_as, query_count};
+
+const NOVEL_ADMIN_FIELD: &str = "articleid, articlename, author, intro, keywords, sortid, fullflag, display, lastupdate, lastchapter, lastchapterid";
+
+/// 后台可以修改的小说信息
+#[derive(Debug)]
+pub(crate) struct NovelEdit {
+    pub(crate) articlename: String,
+    pub(crate) author: String,
+    pub(crate) intro: String,
+    pub(crate) keywords: String,
+    pub(crate) sortid: u8,
+    pub(crate) fullflag: bool,
+}
+
+/// 重新统计的结果
+#[derive(Debug, Serialize)]
+pub(crate) struct NovelStats {
+    lastchapter: String,
+    lastchapterid: u64,
+    chapters: u64,
+    words: u64,
+}
+
+#[derive(Debug, sea_orm::FromQueryResult)]
+struct LastChapter {
+    chapterid: u64,
+    chaptername: String,
+}
+
+fn fill_urls(rows: &mut [NovelAdmin]) {
+    for row in rows.iter_mut() {
+        row.info_url = get_config().info_url(get_config().new_id(row.articleid));
+    }
+}
+
+/// 按ID、书名或作者查找 关键字为空时列出全部 隐藏的小说也列出
+pub(crate) async fn search_novels(keyword: &str, page: u64, per_page: u64) -> Result<(u64, Vec<NovelAdmin>), DbErr> {
+    let table = format!("{}article_article", get_config().prefix);
+    let keyword = keyword.trim();
+    let (filter, values): (&str, Vec<Value>) = if keyword.is_empty() {
+        ("1 = 1", vec![])
+    } else {
+        let id = keyword.parse::<u64>().unwrap_or(0);
+        (
+            "(articleid = ? OR articlename LIKE CONCAT('%',?, '%') OR author LIKE CONCAT('%',?, '%'))",
+            vec![id.into(), keyword.into(), keyword.into()],
+        )
+    };
+    let count = query_count(
+        format!("SELECT COUNT(*) AS cnt FROM {} WHERE {}", table, filter).as_str(),
+        Some(Values(values.clone())),
+    ).await?;
+    let offset = page.saturating_sub(1).saturating_mul(per_page);
+    let mut row_values = values;
+    row_values.push(per_page.into());
+    row_values.push(offset.into());
+    let mut rows: Vec<NovelAdmin> = query_all_as(
+        format!("SELECT {} FROM {} WHERE {} ORDER BY articleid DESC LIMIT ? OFFSET ?", NOVEL_ADMIN_FIELD, table, filter).as_str(),
+        Some(Values(row_values)),
+    ).await?;
+    fill_urls(&mut rows);
+    Ok((count, rows))
+}
+
+pub(crate) async fn get_novel(source_id: u64) -> Result<Option<NovelAdmin>, DbErr> {
+    let sql = format!("SELECT {} FROM {}article_article WHERE articleid = ? LIMIT 1", NOVEL_ADMIN_FIELD, get_config().prefix);
+    let mut row = get_one_as::<NovelAdmin>(&sql, Some(Values(vec![source_id.into()]))).await?;
+    if let Some(row) = row.as_mut() {
+        fill_urls(std::slice::from_mut(row));
+    }
+    Ok(row)
+}
+
+/// 修改小说信息 返回是否找到该小说
+pub(crate) async fn update_novel(source_id: u64, edit: &NovelEdit) -> Result<bool, DbErr> {
+    if get_novel(source_id).await?.is_none() {
+        return Ok(false);
+    }
+    let sql = format!(
+        "UPDATE {}article_article SET articlename = ?, author = ?, intro = ?, keywords = ?, sortid = ?, fullflag = ? WHERE articleid = ?",
+        get_config().prefix
+    );
+    exec_sql(&sql, Some(Values(vec![
+        edit.articlename.as_str().into(),
+        edit.author.as_str().into(),
+        edit.intro.as_str().into(),
+        edit.keywords.as_str().into(),
+        edit.sortid.into(),
+        (edit.fullflag as u8).into(),
+        source_id.into(),
+    ]))).await?;
+    purge_novel_cache_all(source_id).await;
+    Ok(true)
+}
+
+/// 批量隐藏或显示 display 为 1 时前台不显示 返回修改的条数
+pub(crate) async fn set_display(ids: &[u64], hidden: bool) -> Result<u64, DbErr> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let sql = format!(
+        "UPDATE {}article_article SET display = ? WHERE articleid IN ({})",
+        get_config().prefix,
+        vec!["?"; ids.len()].join(", ")
+    );
+    let mut values: Vec<Value> = vec![(hidden as u8).into()];
+    values.extend(ids.iter().map(|id| Value::from(*id)));
+    let n = exec_sql(&sql, Some(Values(values))).await?;
+    for id in ids {
+        purge_novel_cache_all(*id).await;
+    }
+    Ok(n)
+}
+
+/// 按章节表重新统计最新章节、章节数和字数 分卷不计入 小说不存在时返回 None
+pub(crate) async fn recount_novel(source_id: u64) -> Result<Option<NovelStats>, DbErr> {
+    if get_novel(source_id).await?.is_none() {
+        return Ok(None);
+    }
+    let chapter_table = get_config().get_chapter_table(source_id);
+    // 杰奇1.7 字数字段为 size
+    let words_column = if get_config().sys_ver < 2.0 { "size" } else { "words" };
+    let last = get_one_as::<LastChapter>(
+        format!("SELECT chapterid, chaptername FROM {} WHERE articleid = ? AND chaptertype = 0 ORDER BY chapterorder DESC, chapterid DESC LIMIT 1", chapter_table).as_str(),
+        Some(Values(vec![source_id.into()])),
+    ).await?;
+    let chapters = query_count(
+        format!("SELECT COUNT(*) AS cnt FROM {} WHERE articleid = ? AND chaptertype = 0", chapter_table).as_str(),
+        Some(Values(vec![source_id.into()])),
+    ).await?;
+    let words = query_count(
+        format!("SELECT CAST(COALESCE(SUM({}), 0) AS SIGNED) AS cnt FROM {} WHERE articleid = ? AND chaptertype = 0", words_column, chapter_table).as_str(),
+        Some(Values(vec![source_id.into()])),
+    ).await?;
+    let stats = NovelStats {
+        lastchapterid: last.as_ref().map(|c| c.chapterid).unwrap_or(0),
+        lastchapter: last.map(|c| c.chaptername).unwrap_or_default(),
+        chapters,
+        words,
+    };
+    let sql = format!(
+        "UPDATE {}article_article SET lastchapter = ?, lastchapterid = ?, chapters = ?, {} = ? WHERE articleid = ?",
+        get_config().prefix,
+        words_column
+    );
+    exec_sql(&sql, Some(Values(vec![
+        stats.lastchapter.as_str().into(),
+        stats.lastchapterid.into(),
+        stats.chapters.into(),
+        stats.words.into(),
+        source_id.into(),
+    ]))).await?;
+    purge_novel_cache_all(source_id).await;
+    Ok(Some(stats))
+}
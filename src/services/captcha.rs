@@ -0,0 +1,239 @@
+use rand::Rng;
+use axum_extra::extract::CookieJar;
+use crate::utils::conf::get_config;
+use crate::utils::redis::store::{get_value, incr_value, set_value, take_value};
+use crate::utils::templates::str::{random_string, RANDOM_CHARS};
+
+/// 区分浏览器的 cookie 验证码答案按 cookie + 页面里的 captcha_id 保存
+pub(crate) const CAPTCHA_COOKIE: &str = "ss_captcha";
+const KEY_PREFIX: &str = "novel:captcha:";
+const SEARCH_PREFIX: &str = "novel:search_count:";
+const SEARCH_LAST_PREFIX: &str = "novel:search_last:";
+/// 搜索次数的统计时长
+const SEARCH_WINDOW: u64 = 3600;
+
+/// 验证码使用的字符 去掉了 0O 1I 2Z 5S B8 G6 这类容易看错的
+const CAPTCHA_CHARS: [char; 25] = [
+    'A', 'C', 'D', 'E', 'F', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'R',
+    'T', 'U', 'V', 'W', 'X', 'Y', '3', '4', '6', '7', '8', '9',
+];
+
+/// 5x7 点阵字形 与 CAPTCHA_CHARS 一一对应 每行低5位从左到右
+const GLYPHS: [[u8; 7]; 25] = [
+    [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // A
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110], // C
+    [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110], // D
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111], // E
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000], // F
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // H
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100], // J
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001], // K
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111], // L
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001], // M
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001], // N
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000], // P
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001], // R
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // T
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // U
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // V
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010], // W
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001], // X
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100], // Y
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110], // 3
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010], // 4
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110], // 6
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000], // 7
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110], // 8
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100], // 9
+];
+
+/// 每个字符占的宽度 字形放大倍数
+const CELL_WIDTH: usize = 26;
+const SCALE: f32 = 4.0;
+const HEIGHT: usize = 48;
+
+/// 新的 cookie 值或 captcha_id
+pub(crate) fn new_id() -> String {
+    random_string(&RANDOM_CHARS, 16)
+}
+
+/// cookie 和 captcha_id 只能是16位字母数字 其它格式不查
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 16 && id.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// 表单是否需要验证码 register / login
+pub(crate) fn is_required(form: &str) -> bool {
+    match form {
+        "register" => get_config().captcha.register,
+        "login" => get_config().captcha.login,
+        _ => true,
+    }
+}
+
+/// 验证码图片和隐藏的 captcha_id 字段 输入框由模板自己写 name 为 captcha
+pub(crate) fn captcha_html() -> String {
+    let id = new_id();
+    format!(
+        r#"<img src="/captcha?id={id}" class="captcha-img" alt="验证码" title="看不清？点击换一张" style="cursor:pointer;vertical-align:middle" onclick="this.src='/captcha?id={id}&t='+Date.now()"><input type="hidden" name="captcha_id" value="{id}">"#
+    )
+}
+
+fn captcha_key(client: &str, id: &str) -> String {
+    format!("{}{}:{}", KEY_PREFIX, client, id)
+}
+
+/// 生成新的验证码 保存答案并返回 PNG 图片 同一个 id 再次请求时覆盖旧答案
+pub(crate) async fn create_captcha(client: &str, id: &str) -> Option<Vec<u8>> {
+    if !is_valid_id(client) || !is_valid_id(id) {
+        return None;
+    }
+    let length = get_config().captcha.length.clamp(4, 8) as usize;
+    let answer = random_string(&CAPTCHA_CHARS, length);
+    let png = render_png(&answer)?;
+    let ttl = get_config().captcha.ttl.max(30) as u64;
+    set_value(&captcha_key(client, id), &answer, ttl).await;
+    Some(png)
+}
+
+/// 校验验证码 不区分大小写 不论对错答案只能用一次
+pub(crate) async fn verify_captcha(client: &str, id: &str, input: &str) -> bool {
+    if !is_valid_id(client) || !is_valid_id(id) {
+        return false;
+    }
+    let input = input.trim();
+    if input.is_empty() {
+        return false;
+    }
+    take_value(&captcha_key(client, id))
+        .await
+        .is_some_and(|answer| answer.eq_ignore_ascii_case(input))
+}
+
+/// 用请求里的 cookie 校验表单提交的验证码
+pub(crate) async fn verify_form(jar: &CookieJar, id: Option<&str>, input: Option<&str>) -> bool {
+    let Some(client) = jar.get(CAPTCHA_COOKIE) else {
+        return false;
+    };
+    verify_captcha(client.value(), id.unwrap_or(""), input.unwrap_or("")).await
+}
+
+/// 记一次搜索 返回一小时内的搜索次数 取不到IP时(没有反向代理)返回0
+pub(crate) async fn incr_search_count(ip: &str) -> u32 {
+    if ip.is_empty() {
+        return 0;
+    }
+    incr_value(&format!("{}{}", SEARCH_PREFIX, ip), SEARCH_WINDOW).await
+}
+
+/// 通过验证码后重新计数
+pub(crate) async fn reset_search_count(ip: &str) {
+    let key = format!("{}{}", SEARCH_PREFIX, ip);
+    take_value(&key).await;
+}
+
+/// 上次搜索的关键字摘要 没有 cookie 的阅读书源和 OPDS 按IP记录搜索间隔
+pub(crate) async fn get_last_search(ip: &str) -> Option<String> {
+    if ip.is_empty() {
+        return None;
+    }
+    get_value(&format!("{}{}", SEARCH_LAST_PREFIX, ip)).await
+}
+
+/// 记录上次搜索 ttl 为搜索间隔
+pub(crate) async fn set_last_search(ip: &str, keyword_hash: &str, ttl: u64) {
+    if ip.is_empty() || ttl == 0 {
+        return;
+    }
+    set_value(&format!("{}{}", SEARCH_LAST_PREFIX, ip), keyword_hash, ttl).await;
+}
+
+/// 画验证码 每个字符随机旋转和上下偏移 整体做正弦扭曲 再加干扰线和噪点
+fn render_png(answer: &str) -> Option<Vec<u8>> {
+    let mut rng = rand::rng();
+    let glyphs: Vec<usize> = answer
+        .chars()
+        .filter_map(|c| CAPTCHA_CHARS.iter().position(|&x| x == c))
+        .collect();
+    let width = CELL_WIDTH * glyphs.len() + 16;
+    let bg = [
+        rng.random_range(225..=250u8),
+        rng.random_range(225..=250u8),
+        rng.random_range(225..=250u8),
+    ];
+    let mut pixels = vec![0u8; width * HEIGHT * 3];
+    for px in pixels.chunks_mut(3) {
+        let n = rng.random_range(0..=12u8);
+        px.copy_from_slice(&[bg[0] - n, bg[1] - n, bg[2] - n]);
+    }
+    // 每个字符的中心 旋转角度 颜色
+    let chars: Vec<(usize, f32, f32, f32, [u8; 3])> = glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, &g)| {
+            let cx = 8.0 + CELL_WIDTH as f32 * (i as f32 + 0.5) + rng.random_range(-3.0..3.0);
+            let cy = HEIGHT as f32 / 2.0 + rng.random_range(-5.0..5.0);
+            let color = [rng.random_range(20..120u8), rng.random_range(20..120u8), rng.random_range(20..120u8)];
+            (g, cx, cy, rng.random_range(-0.3..0.3f32), color)
+        })
+        .collect();
+    let (amp_x, freq_x, phase_x) = (rng.random_range(1.0..2.0f32), rng.random_range(0.05..0.1f32), rng.random_range(0.0..6.3f32));
+    let (amp_y, freq_y, phase_y) = (rng.random_range(1.5..3.0f32), rng.random_range(0.03..0.06f32), rng.random_range(0.0..6.3f32));
+    for y in 0..HEIGHT {
+        for x in 0..width {
+            let wx = x as f32 + amp_x * (y as f32 * freq_x + phase_x).sin();
+            let wy = y as f32 + amp_y * (x as f32 * freq_y + phase_y).sin();
+            for &(g, cx, cy, angle, color) in &chars {
+                let (dx, dy) = (wx - cx, wy - cy);
+                if dx.abs() > CELL_WIDTH as f32 {
+                    continue;
+                }
+                // 反向旋转回字形坐标
+                let (sin, cos) = angle.sin_cos();
+                let gx = (dx * cos + dy * sin) / SCALE + 2.5;
+                let gy = (-dx * sin + dy * cos) / SCALE + 3.5;
+                if gx < 0.0 || gy < 0.0 || gx >= 5.0 || gy >= 7.0 {
+                    continue;
+                }
+                if GLYPHS[g][gy as usize] & (0b10000 >> gx as usize) != 0 {
+                    let i = (y * width + x) * 3;
+                    pixels[i..i + 3].copy_from_slice(&color);
+                    break;
+                }
+            }
+        }
+    }
+    for _ in 0..rng.random_range(3..=5) {
+        let color = [rng.random_range(60..180u8), rng.random_range(60..180u8), rng.random_range(60..180u8)];
+        let (x0, y0) = (rng.random_range(0..width / 3) as f32, rng.random_range(0..HEIGHT) as f32);
+        let (x1, y1) = (rng.random_range(width * 2 / 3..width) as f32, rng.random_range(0..HEIGHT) as f32);
+        let bend = rng.random_range(-8.0..8.0f32);
+        let steps = width * 2;
+        for s in 0..=steps {
+            let t = s as f32 / steps as f32;
+            let x = (x0 + (x1 - x0) * t) as usize;
+            let y = y0 + (y1 - y0) * t + bend * (t * std::f32::consts::PI).sin();
+            if x < width && y >= 0.0 && (y as usize) < HEIGHT {
+                let i = (y as usize * width + x) * 3;
+                pixels[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+    for _ in 0..width * HEIGHT / 25 {
+        let i = (rng.random_range(0..HEIGHT) * width + rng.random_range(0..width)) * 3;
+        let color = [rng.random_range(0..=255u8), rng.random_range(0..=255u8), rng.random_range(0..=255u8)];
+        pixels[i..i + 3].copy_from_slice(&color);
+    }
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, width as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let res = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels));
+    if let Err(e) = res {
+        eprintln!("captcha png error: {e:?}");
+        return None;
+    }
+    Some(buf)
+}
@@ -0,0 +1,109 @@
+use sea_orm::{DbErr, Values};
+use crate::models::user::User;
+use crate::services::mail::{send_mail, MailError};
+use crate::services::mail_token::{create_token, try_cooldown, MailToken, TokenPurpose};
+use crate::services::user::{timestamp_10, CreateUserError};
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_count};
+
+/// 同一用户同一类邮件的最短发送间隔
+const MAIL_COOLDOWN: u64 = 60;
+
+/// 邮件发送结果
+#[derive(Debug)]
+pub(crate) enum SendResult {
+    Sent,
+    /// 发送太频繁
+    TooFrequent,
+}
+
+/// 当前邮箱是否已验证 改过邮箱后需要重新验证
+pub(crate) async fn is_email_verified(user_id: u64) -> Result<bool, DbErr> {
+    let sql = format!(
+        "SELECT COUNT(*) AS cnt FROM {prefix}system_users u INNER JOIN {prefix}user_email e ON e.userid = u.uid AND e.email = u.email WHERE u.uid = ?",
+        prefix = get_config().prefix
+    );
+    Ok(query_count(&sql, Some(Values(vec![user_id.into()]))).await? > 0)
+}
+
+async fn mark_email_verified(user_id: u64, email: &str) -> Result<(), DbErr> {
+    let sql = format!(
+        "INSERT INTO {}user_email (userid, email, verified) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE email = VALUES(email), verified = VALUES(verified)",
+        get_config().prefix
+    );
+    exec_sql(&sql, Some(Values(vec![user_id.into(), email.into(), timestamp_10().into()]))).await?;
+    Ok(())
+}
+
+/// 邮件里链接的有效时间 按分钟显示
+fn ttl_minutes() -> u32 {
+    get_config().mail.token_ttl.max(60) / 60
+}
+
+/// 发送找回密码邮件 base_url 为站点根地址
+pub(crate) async fn send_reset_mail(user: &User, base_url: &str) -> Result<SendResult, MailError> {
+    if !try_cooldown(TokenPurpose::Reset, user.uid, MAIL_COOLDOWN).await {
+        return Ok(SendResult::TooFrequent);
+    }
+    let token = create_token(TokenPurpose::Reset, &MailToken { userid: user.uid, email: user.email.clone() }).await;
+    let body = format!(
+        "{} 您好：\n\n您在 {} 申请了找回密码，请在 {} 分钟内打开下面的链接设置新密码：\n\n{}/password/reset?token={}\n\n如果不是您本人操作，请忽略这封邮件，您的密码不会改变。",
+        user.uname, get_config().site_name, ttl_minutes(), base_url, token
+    );
+    send_mail(&user.email, &format!("{} 找回密码", get_config().site_name), &body).await?;
+    Ok(SendResult::Sent)
+}
+
+/// 发送验证邮箱的邮件
+pub(crate) async fn send_verify_mail(user: &User, base_url: &str) -> Result<SendResult, MailError> {
+    if !try_cooldown(TokenPurpose::Verify, user.uid, MAIL_COOLDOWN).await {
+        return Ok(SendResult::TooFrequent);
+    }
+    let token = create_token(TokenPurpose::Verify, &MailToken { userid: user.uid, email: user.email.clone() }).await;
+    let body = format!(
+        "{} 您好：\n\n请在 {} 分钟内打开下面的链接验证您在 {} 的邮箱：\n\n{}/email/confirm?token={}\n\n如果不是您本人操作，请忽略这封邮件。",
+        user.uname, ttl_minutes(), get_config().site_name, base_url, token
+    );
+    send_mail(&user.email, &format!("{} 验证邮箱", get_config().site_name), &body).await?;
+    Ok(SendResult::Sent)
+}
+
+/// 修改邮箱 确认邮件发到新邮箱 打开链接后才会修改
+pub(crate) async fn send_change_mail(user: &User, email: &str, base_url: &str) -> Result<SendResult, MailError> {
+    if !try_cooldown(TokenPurpose::ChangeEmail, user.uid, MAIL_COOLDOWN).await {
+        return Ok(SendResult::TooFrequent);
+    }
+    let token = create_token(TokenPurpose::ChangeEmail, &MailToken { userid: user.uid, email: email.to_string() }).await;
+    let body = format!(
+        "{} 您好：\n\n您在 {} 申请把邮箱改为 {}，请在 {} 分钟内打开下面的链接确认：\n\n{}/email/confirm?token={}\n\n如果不是您本人操作，请忽略这封邮件。",
+        user.uname, get_config().site_name, email, ttl_minutes(), base_url, token
+    );
+    send_mail(email, &format!("{} 修改邮箱", get_config().site_name), &body).await?;
+    Ok(SendResult::Sent)
+}
+
+/// 邮箱是否已被其他用户使用
+pub(crate) async fn is_email_taken(email: &str, user_id: u64) -> Result<bool, DbErr> {
+    let sql = format!("SELECT COUNT(*) AS cnt FROM {}system_users WHERE email = ? AND uid <> ?", get_config().prefix);
+    Ok(query_count(&sql, Some(Values(vec![email.into(), user_id.into()]))).await? > 0)
+}
+
+/// 确认验证邮箱 token 里的邮箱已不是当前邮箱时不通过
+pub(crate) async fn confirm_verify(token: &MailToken, user: &User) -> Result<bool, DbErr> {
+    if !token.email.eq_ignore_ascii_case(&user.email) {
+        return Ok(false);
+    }
+    mark_email_verified(user.uid, &user.email).await?;
+    Ok(true)
+}
+
+/// 确认修改邮箱 新邮箱同时记为已验证
+pub(crate) async fn confirm_change(token: &MailToken) -> Result<(), CreateUserError> {
+    if is_email_taken(&token.email, token.userid).await? {
+        return Err(CreateUserError::EmailAlreadyExist);
+    }
+    let sql = format!("UPDATE {}system_users SET email = ? WHERE uid = ?", get_config().prefix);
+    exec_sql(&sql, Some(Values(vec![token.email.as_str().into(), token.userid.into()]))).await?;
+    mark_email_verified(token.userid, &token.email).await?;
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use chrono::{TimeZone, Utc};
+use html_escape::encode_text;
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, Mutex};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use crate::models::novel::{Novel, NovelChapter};
+use crate::services::novel::{get_chapter_content, get_chapter_rows};
+use crate::utils::conf::{get_config, multi_replace};
+use crate::utils::request::FETCHER;
+use crate::utils::text::str_to_p;
+
+/// EPUB 缓存目录 按 source_id/1000 分目录存放
+const EPUB_DIR: &str = "cache/epub";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+/// Text 目录下的页面引用样式的相对路径
+const TEXT_CSS: &str = "../Styles/style.css";
+
+const STYLE_CSS: &str = "body{margin:0 5%;line-height:1.8;}h1,h2{text-align:center;margin:1em 0;}p{text-indent:2em;margin:0.5em 0;}.cover{text-align:center;}.cover img{max-width:100%;max-height:100%;}.meta{text-align:center;color:#666;}";
+
+/// 分卷及其下的章节 没有分卷的章节 volume 为 None
+struct VolumeGroup<'a> {
+    volume: Option<&'a str>,
+    chapters: Vec<(usize, &'a NovelChapter)>,
+}
+
+/// 缓存文件路径 以最新章节ID作为版本 书籍更新后自动失效
+pub(crate) fn epub_file_path(source_id: u64, last_chapter_id: u64) -> PathBuf {
+    Path::new(EPUB_DIR)
+        .join((source_id / 1000).to_string())
+        .join(format!("{}_{}.epub", source_id, last_chapter_id))
+}
+
+/// 正在生成的EPUB 同一本书同时只生成一次 其它请求等待后直接用生成好的文件
+static BUILDING: Lazy<Mutex<HashMap<u64, Arc<Mutex<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 写入压缩包的一个文件 (路径, 内容, 是否压缩)
+type ZipEntry = (String, Vec<u8>, bool);
+
+/// 取得小说的EPUB文件 缓存命中直接返回路径 否则重新生成
+///
+/// row 必须是经过 novel_mapping 处理过的小说数据
+pub(crate) async fn get_epub(row: &Novel, url: &str) -> io::Result<PathBuf> {
+    let path = epub_file_path(row.source_id, row.lastchapterid);
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Ok(path);
+    }
+    let lock = BUILDING.lock().await.entry(row.source_id).or_default().clone();
+    let _guard = lock.lock().await;
+    // 等待期间可能已经由别的请求生成好了
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Ok(path);
+    }
+    let res = build_epub_file(row, url, &path).await;
+    BUILDING.lock().await.remove(&row.source_id);
+    res.map(|_| path)
+}
+
+async fn build_epub_file(row: &Novel, url: &str, path: &Path) -> io::Result<()> {
+    let chapter_rows = get_chapter_rows(url, get_config().cache.info, row.source_id).await;
+    if chapter_rows.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "章节列表为空"));
+    }
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    // 先写临时文件再改名 避免并发请求读到写了一半的文件
+    let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4().to_simple()));
+    // 压缩和写文件在阻塞线程里做 章节内容逐个读取后通过 channel 传过去
+    let (tx, mut rx) = mpsc::channel::<ZipEntry>(16);
+    let writer_path = tmp.clone();
+    let writer = tokio::task::spawn_blocking(move || -> io::Result<()> {
+        let mut zip = ZipWriter::new(File::create(&writer_path)?);
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        while let Some((name, bytes, compress)) = rx.blocking_recv() {
+            zip.start_file(name, if compress { deflated } else { stored })?;
+            zip.write_all(&bytes)?;
+        }
+        zip.finish()?;
+        Ok(())
+    });
+    // 写入线程出错时 channel 会关闭 以 join 的结果为准
+    let _ = send_entries(row, &chapter_rows, &tx).await;
+    drop(tx);
+    let res = match writer.await {
+        Ok(res) => res,
+        Err(e) => Err(io::Error::other(e)),
+    };
+    if let Err(e) = res {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    tokio::fs::rename(&tmp, path).await?;
+    let current = path.to_path_buf();
+    let source_id = row.source_id;
+    let _ = tokio::task::spawn_blocking(move || remove_stale_epub(&current, source_id)).await;
+    Ok(())
+}
+
+/// 按顺序生成压缩包里的各个文件
+async fn send_entries(row: &Novel, chapter_rows: &[NovelChapter], tx: &mpsc::Sender<ZipEntry>) -> Result<(), mpsc::error::SendError<ZipEntry>> {
+    let send = |name: String, bytes: Vec<u8>, compress: bool| tx.send((name, bytes, compress));
+    // mimetype 必须是第一个文件且不压缩
+    send("mimetype".to_string(), b"application/epub+zip".to_vec(), false).await?;
+    send("META-INF/container.xml".to_string(), CONTAINER_XML.as_bytes().to_vec(), true).await?;
+    send("OEBPS/Styles/style.css".to_string(), STYLE_CSS.as_bytes().to_vec(), true).await?;
+
+    let title = encode_text(&row.articlename).to_string();
+    let author = encode_text(&row.author).to_string();
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut cover_meta = "";
+
+    if let Some((bytes, ext, media_type)) = load_cover(&row.img_url).await {
+        send(format!("OEBPS/Images/cover.{}", ext), bytes, false).await?;
+        let body = format!(r#"<div class="cover"><img src="../Images/cover.{}" alt="{}"/></div>"#, ext, title);
+        send("OEBPS/Text/cover.xhtml".to_string(), xhtml_page(&title, TEXT_CSS, &body).into_bytes(), true).await?;
+        manifest.push_str(&format!(r#"<item id="cover-image" href="Images/cover.{}" media-type="{}" properties="cover-image"/>"#, ext, media_type));
+        manifest.push_str(r#"<item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>"#);
+        spine.push_str(r#"<itemref idref="cover"/>"#);
+        cover_meta = r#"<meta name="cover" content="cover-image"/>"#;
+    }
+
+    // intro_des 是先转义再截断的 可能截在实体中间 这里用原始简介自己转义
+    let intro_lines = row
+        .intro
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let description = encode_text(&row.intro.split_whitespace().collect::<Vec<_>>().join(" ")).to_string();
+    let intro = format!(
+        r#"<h1>{title}</h1><p class="meta">作者：{author}</p><p class="meta">分类：{sort}</p><p class="meta">{isfull}</p>{intro}"#,
+        title = title,
+        author = author,
+        sort = encode_text(&row.sortname),
+        isfull = encode_text(&row.isfull),
+        intro = str_to_p(&encode_text(&intro_lines)),
+    );
+    send("OEBPS/Text/intro.xhtml".to_string(), xhtml_page(&title, TEXT_CSS, &intro).into_bytes(), true).await?;
+    manifest.push_str(r#"<item id="intro" href="Text/intro.xhtml" media-type="application/xhtml+xml"/>"#);
+    spine.push_str(r#"<itemref idref="intro"/><itemref idref="nav"/>"#);
+
+    let groups = group_by_volume(chapter_rows);
+    for group in &groups {
+        for (i, chapter) in &group.chapters {
+            let content = multi_replace(&get_chapter_content(row.source_id, chapter.source_id).await);
+            let lines = content
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let chapter_title = encode_text(&chapter.chaptername).to_string();
+            let body = format!("<h2>{}</h2>{}", chapter_title, str_to_p(&encode_text(&lines)));
+            send(format!("OEBPS/Text/chapter_{}.xhtml", i), xhtml_page(&chapter_title, TEXT_CSS, &body).into_bytes(), true).await?;
+            manifest.push_str(&format!(r#"<item id="c{i}" href="Text/chapter_{i}.xhtml" media-type="application/xhtml+xml"/>"#, i = i));
+            spine.push_str(&format!(r#"<itemref idref="c{}"/>"#, i));
+        }
+    }
+
+    send("OEBPS/nav.xhtml".to_string(), nav_page(&title, &groups).into_bytes(), true).await?;
+
+    let modified = Utc
+        .timestamp_opt(row.lastupdate as i64, 0)
+        .single()
+        .unwrap_or_else(Utc::now)
+        .format("%Y-%m-%dT%H:%M:%SZ");
+    let opf = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="bookid" xml:lang="zh-CN">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:identifier id="bookid">{identifier}</dc:identifier>
+<dc:title>{title}</dc:title>
+<dc:language>zh-CN</dc:language>
+<dc:creator>{author}</dc:creator>
+<dc:subject>{sort}</dc:subject>
+<dc:description>{intro}</dc:description>
+<dc:publisher>{publisher}</dc:publisher>
+<meta property="dcterms:modified">{modified}</meta>
+{cover_meta}
+</metadata>
+<manifest>
+<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+<item id="style" href="Styles/style.css" media-type="text/css"/>
+{manifest}
+</manifest>
+<spine>{spine}</spine>
+</package>"#,
+        identifier = encode_text(&format!("{}{}", get_config().site_url, row.info_url)),
+        title = title,
+        author = author,
+        sort = encode_text(&row.sortname),
+        intro = description,
+        publisher = encode_text(&get_config().site_name),
+        modified = modified,
+        cover_meta = cover_meta,
+        manifest = manifest,
+        spine = spine,
+    );
+    send("OEBPS/content.opf".to_string(), opf.into_bytes(), true).await?;
+    Ok(())
+}
+
+/// 按分卷把章节分组 chaptertype = 1 为分卷
+fn group_by_volume(chapter_rows: &[NovelChapter]) -> Vec<VolumeGroup<'_>> {
+    let mut groups = vec![VolumeGroup { volume: None, chapters: Vec::new() }];
+    for (i, chapter) in chapter_rows.iter().enumerate() {
+        if chapter.chaptertype == 1 {
+            groups.push(VolumeGroup { volume: Some(chapter.chaptername.as_str()), chapters: Vec::new() });
+        } else if let Some(last) = groups.last_mut() {
+            last.chapters.push((i, chapter));
+        }
+    }
+    groups
+}
+
+fn nav_page(title: &str, groups: &[VolumeGroup]) -> String {
+    let mut ol = String::from("<ol>");
+    for group in groups {
+        if group.chapters.is_empty() {
+            continue;   // 空分卷在 nav 中不合法 直接跳过
+        }
+        if let Some(volume) = group.volume {
+            ol.push_str(&format!("<li><span>{}</span><ol>", encode_text(volume)));
+        }
+        for (i, chapter) in &group.chapters {
+            ol.push_str(&format!(
+                r#"<li><a href="Text/chapter_{}.xhtml">{}</a></li>"#,
+                i,
+                encode_text(&chapter.chaptername)
+            ));
+        }
+        if group.volume.is_some() {
+            ol.push_str("</ol></li>");
+        }
+    }
+    ol.push_str("</ol>");
+    let body = format!(r#"<nav epub:type="toc" id="toc"><h1>{}</h1>{}</nav>"#, title, ol);
+    xhtml_page(title, "Styles/style.css", &body)
+}
+
+fn xhtml_page(title: &str, css: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="zh-CN" lang="zh-CN">
+<head><meta charset="utf-8"/><title>{}</title><link rel="stylesheet" type="text/css" href="{}"/></head>
+<body>{}</body>
+</html>"#,
+        title,
+        css,
+        body
+    )
+}
+
+/// 读取封面 返回 (内容, 扩展名, media-type) 读取失败则不生成封面
+async fn load_cover(img_url: &str) -> Option<(Vec<u8>, &'static str, &'static str)> {
+    let bytes = if img_url.starts_with("http://") || img_url.starts_with("https://") {
+        FETCHER.get_bytes(img_url).await.ok()?
+    } else if let Some(path) = img_url.strip_prefix("/static/") {
+        tokio::fs::read(Path::new("public").join(path)).await.ok()?
+    } else {
+        tokio::fs::read(img_url).await.ok()?
+    };
+    let (ext, media_type) = match bytes.as_slice() {
+        [0x89, b'P', b'N', b'G', ..] => ("png", "image/png"),
+        [b'G', b'I', b'F', ..] => ("gif", "image/gif"),
+        [0xFF, 0xD8, ..] => ("jpg", "image/jpeg"),
+        _ => return None,
+    };
+    Some((bytes, ext, media_type))
+}
+
+/// 删除一本书的全部缓存文件 章节内容修改后调用
+pub(crate) fn remove_epub(source_id: u64) {
+    let dir = Path::new(EPUB_DIR).join((source_id / 1000).to_string());
+    remove_epub_files(&dir, source_id, None);
+}
+
+/// 删除同一本书旧版本的缓存文件
+fn remove_stale_epub(current: &Path, source_id: u64) {
+    let Some(dir) = current.parent() else { return; };
+    remove_epub_files(dir, source_id, Some(current));
+}
+
+fn remove_epub_files(dir: &Path, source_id: u64, keep: Option<&Path>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return; };
+    let prefix = format!("{}_", source_id);
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue; };
+        if name.starts_with(&prefix) && name.ends_with(".epub") && Some(path.as_path()) != keep {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::convert::Infallible;
+use sea_orm::Values;
+use crate::services::novel::{get_chapter_content, get_chapter_rows, get_novel_info, lastupdate_sql};
+use crate::services::user::timestamp_10;
+use crate::utils::conf::{get_config, multi_replace};
+use crate::utils::redis::conn::{cache_key, get_cache_rows, get_cache_text};
+use crate::utils::text::{time_to_w3c, xml_escape};
+
+/// 单本小说 Atom 中的最新章节数
+const FEED_CHAPTERS: usize = 20;
+
+struct AtomEntry {
+    title: String,
+    url: String,
+    updated: u64,
+    author: String,
+    summary: String,
+}
+
+/// 全站最近更新 与首页 lastupdate 使用同一条查询
+pub(crate) async fn get_site_feed(url: &str, base: &str) -> String {
+    let key = cache_key("feed", base, "site", &None);
+    let Ok(xml) = get_cache_text::<_, _, Infallible>(&key, get_config().cache.info as u64, || async {
+        let rows = get_cache_rows(lastupdate_sql(), url, get_config().cache.info as u64, None).await;
+        let entries: Vec<AtomEntry> = rows.into_iter().map(|row| AtomEntry {
+            title: format!("{} {}", row.articlename, row.lastchapter),
+            url: format!("{}{}", base, row.last_url),
+            updated: row.lastupdate,
+            author: row.author,
+            summary: row.intro.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(200).collect(),
+        }).collect();
+        Ok(atom_feed(
+            &format!("{} - 最近更新", get_config().site_name),
+            &format!("{}{}", base, get_config().rewrite.feed_url),
+            &format!("{}/", base),
+            "",
+            &entries,
+        ))
+    }).await;
+    xml
+}
+
+/// 单本小说的最新章节 小说不存在时返回 None
+pub(crate) async fn get_novel_feed(url: &str, base: &str, source_id: u64) -> Option<String> {
+    let row = get_novel_info(url, get_config().cache.info, source_id).await.into_iter().next()?;
+    let key = cache_key("feed", base, "novel", &Some(Values(vec![source_id.into()])));
+    let Ok(xml) = get_cache_text::<_, _, Infallible>(&key, get_config().cache.info as u64, || async {
+        let chapter_rows = get_chapter_rows(url, get_config().cache.info, source_id).await;
+        let mut entries = Vec::with_capacity(FEED_CHAPTERS);
+        // chaptertype = 1 为分卷 不是章节
+        for chapter in chapter_rows.iter().rev().filter(|c| c.chaptertype != 1).take(FEED_CHAPTERS) {
+            entries.push(AtomEntry {
+                title: chapter.chaptername.clone(),
+                url: format!("{}{}", base, chapter.read_url),
+                updated: chapter.lastupdate,
+                author: row.author.clone(),
+                summary: chapter_excerpt(source_id, chapter.source_id).await,
+            });
+        }
+        Ok(atom_feed(
+            &format!("{} - {}", row.articlename, get_config().site_name),
+            &format!("{}{}", base, get_config().novel_feed_url(row.articleid)),
+            &format!("{}{}", base, row.info_url),
+            &row.author,
+            &entries,
+        ))
+    }).await;
+    Some(xml)
+}
+
+/// 章节摘要 取正文前 feed_excerpt 个字 为 0 时不输出
+async fn chapter_excerpt(source_id: u64, source_chapter_id: u64) -> String {
+    let len = get_config().feed_excerpt as usize;
+    if len == 0 {
+        return String::new();
+    }
+    let content = get_chapter_content(source_id, source_chapter_id).await;
+    let text: String = content.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(len).collect();
+    multi_replace(&text)
+}
+
+fn atom_feed(title: &str, self_url: &str, alt_url: &str, author: &str, entries: &[AtomEntry]) -> String {
+    let updated = entries.iter().map(|e| e.updated).max().unwrap_or_else(|| timestamp_10() as u64);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(title)));
+    xml.push_str(&format!("<id>{}</id>\n", xml_escape(self_url)));
+    xml.push_str(&format!("<link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n", xml_escape(self_url)));
+    xml.push_str(&format!("<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n", xml_escape(alt_url)));
+    xml.push_str(&format!("<updated>{}</updated>\n", time_to_w3c(updated as i64)));
+    if !author.is_empty() {
+        xml.push_str(&format!("<author><name>{}</name></author>\n", xml_escape(author)));
+    }
+    for entry in entries {
+        xml.push_str("<entry>");
+        xml.push_str(&format!("<title>{}</title>", xml_escape(&entry.title)));
+        xml.push_str(&format!("<id>{}</id>", xml_escape(&entry.url)));
+        xml.push_str(&format!("<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>", xml_escape(&entry.url)));
+        xml.push_str(&format!("<updated>{}</updated>", time_to_w3c(entry.updated as i64)));
+        xml.push_str(&format!("<author><name>{}</name></author>", xml_escape(&entry.author)));
+        if !entry.summary.is_empty() {
+            xml.push_str(&format!("<summary>{}</summary>", xml_escape(&entry.summary)));
+        }
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
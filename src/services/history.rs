@@ -0,0 +1,117 @@
+use sea_orm::{DbErr, Value, Values};
+use serde::Deserialize;
+use crate::models::user::{ReadLog, ReadLogOnNovel};
+use crate::services::novel::query_novel_process;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_all_as};
+
+/// 每个用户最多保留的阅读记录 与 history.js 的 bookmax 一致
+pub(crate) const HISTORY_MAX: usize = 200;
+/// 章节名最多保留的字符数 与表结构一致
+const CHAPTERNAME_MAX_CHARS: usize = 100;
+
+/// 浏览器提交的阅读记录 ID 为混淆后的ID time 为10位时间戳
+#[derive(Debug, Deserialize)]
+pub(crate) struct HistoryItem {
+    pub(crate) articleid: u64,
+    pub(crate) chapterid: u64,
+    #[serde(default)]
+    pub(crate) chaptername: String,
+    pub(crate) time: u64,
+}
+
+/// 合并浏览器的阅读记录 同一本书以阅读时间较新的为准
+///
+/// 超出 HISTORY_MAX 的旧记录会被删除
+pub(crate) async fn merge_history(user_id: u64, items: &[HistoryItem]) -> Result<(), DbErr> {
+    let now = timestamp_10() as u64;
+    let items: Vec<&HistoryItem> = items
+        .iter()
+        .filter(|i| i.articleid > 0 && i.chapterid > 0)
+        .take(HISTORY_MAX)
+        .collect();
+    if items.is_empty() {
+        return Ok(());
+    }
+    let table = format!("{}article_readlog", get_config().prefix);
+    // 按顺序赋值 readtime 必须最后更新 前面的 IF 才能比较到旧值
+    let sql = format!(
+        "INSERT INTO {} (userid, articleid, chapterid, chaptername, readtime) VALUES {} ON DUPLICATE KEY UPDATE chapterid = IF(VALUES(readtime) > readtime, VALUES(chapterid), chapterid), chaptername = IF(VALUES(readtime) > readtime, VALUES(chaptername), chaptername), readtime = GREATEST(readtime, VALUES(readtime))",
+        table,
+        vec!["(?, ?, ?, ?, ?)"; items.len()].join(", ")
+    );
+    let mut values: Vec<Value> = Vec::with_capacity(items.len() * 5);
+    for item in items {
+        values.push(user_id.into());
+        values.push(get_config().source_id(item.articleid).into());
+        values.push(get_config().source_id(item.chapterid).into());
+        values.push(item.chaptername.trim().chars().take(CHAPTERNAME_MAX_CHARS).collect::<String>().into());
+        values.push(item.time.min(now).into());
+    }
+    exec_sql(sql.as_str(), Some(Values(values))).await?;
+    // MySQL 不能在 DELETE 的子查询里直接查同一张表 需要再包一层
+    let trim_sql = format!(
+        "DELETE FROM {table} WHERE userid = ? AND readtime < (SELECT readtime FROM (SELECT readtime FROM {table} WHERE userid = ? ORDER BY readtime DESC LIMIT 1 OFFSET {offset}) AS t)",
+        table = table,
+        offset = HISTORY_MAX - 1
+    );
+    exec_sql(trim_sql.as_str(), Some(Values(vec![user_id.into(), user_id.into()]))).await?;
+    Ok(())
+}
+
+/// 阅读记录 按阅读时间倒序 小说已删除的记录不返回
+pub(crate) async fn get_history_list(user_id: u64) -> Result<Vec<ReadLogOnNovel>, DbErr> {
+    let sql = format!(
+        "SELECT articleid, chapterid, chaptername, readtime FROM {}article_readlog WHERE userid = ? ORDER BY readtime DESC LIMIT {}",
+        get_config().prefix,
+        HISTORY_MAX
+    );
+    let mut logs: Vec<ReadLog> = query_all_as(sql.as_str(), Some(Values(vec![user_id.into()]))).await?;
+    if logs.is_empty() {
+        return Ok(vec![]);
+    }
+    for log in logs.iter_mut() {
+        log.read_url = get_config().read_url(
+            get_config().new_id(log.articleid),
+            get_config().new_id(log.chapterid),
+            1,
+        );
+    }
+    let novel_sql = format!(
+        "SELECT {} FROM {}article_article WHERE {} AND articleid IN ({})",
+        get_config().get_field(),
+        get_config().prefix,
+        get_config().get_where(),
+        vec!["?"; logs.len()].join(",")
+    );
+    let novel_list = query_novel_process(
+        &novel_sql,
+        Some(Values(logs.iter().map(|l| Value::from(l.articleid)).collect())),
+    ).await?;
+    Ok(logs
+        .into_iter()
+        .filter_map(|log| {
+            novel_list
+                .iter()
+                .find(|n| n.source_id == log.articleid)
+                .cloned()
+                .map(|novel| ReadLogOnNovel { log, novel })
+        })
+        .collect())
+}
+
+/// 删除阅读记录 source_id 为 None 时清空
+pub(crate) async fn delete_history(user_id: u64, source_id: Option<u64>) -> Result<u64, DbErr> {
+    let table = format!("{}article_readlog", get_config().prefix);
+    match source_id {
+        Some(id) => exec_sql(
+            format!("DELETE FROM {} WHERE userid = ? AND articleid = ?", table).as_str(),
+            Some(Values(vec![user_id.into(), id.into()])),
+        ).await,
+        None => exec_sql(
+            format!("DELETE FROM {} WHERE userid = ?", table).as_str(),
+            Some(Values(vec![user_id.into()])),
+        ).await,
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use redis::{AsyncCommands, RedisResult};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::redis::conn::get_redis_conn;
+
+const KEY_PREFIX: &str = "novel:login_fail:";
+
+/// Redis 不可用时的失败记录 只在当前进程内有效
+static MEMORY: Lazy<Mutex<HashMap<String, LoginFail>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 一个 IP 或用户名的登录失败记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct LoginFail {
+    /// ip:xxx 或 user:xxx
+    #[serde(default)]
+    pub(crate) target: String,
+    pub(crate) fails: u32,
+    /// 在这个时间之前不允许再尝试
+    pub(crate) until: u64,
+    /// 记录过期时间 过期后失败次数清零
+    pub(crate) expire: u64,
+}
+
+fn user_target(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+/// 要计数的对象和各自的次数上限 取不到IP时(没有反向代理)只按用户名计数
+fn targets(ip: &str, username: &str) -> Vec<(String, u32)> {
+    let limit = &get_config().login_limit;
+    let mut targets = vec![(user_target(username), limit.user_max)];
+    if !ip.is_empty() {
+        targets.push((format!("ip:{}", ip), limit.ip_max));
+    }
+    targets
+}
+
+/// 登录前检查 被限制时返回还需等待的秒数
+pub(crate) async fn check_login(ip: &str, username: &str) -> Option<u64> {
+    let now = timestamp_10() as u64;
+    let mut wait = 0;
+    for (target, _) in targets(ip, username) {
+        if let Some(fail) = get_fail(&target).await
+            && fail.until > now
+        {
+            wait = wait.max(fail.until - now);
+        }
+    }
+    (wait > 0).then_some(wait)
+}
+
+/// 失败计数和等待时间在 Redis 里一次算完 并发的失败不会互相覆盖
+const RECORD_SCRIPT: &str = r#"
+local fails = 1
+local old = redis.call('GET', KEYS[1])
+if old then
+    local ok, fail = pcall(cjson.decode, old)
+    if ok and type(fail) == 'table' and tonumber(fail.fails) then
+        fails = tonumber(fail.fails) + 1
+    end
+end
+local now, delay, lock, max, window = tonumber(ARGV[2]), tonumber(ARGV[3]), tonumber(ARGV[4]), tonumber(ARGV[5]), tonumber(ARGV[6])
+local wait = lock
+if max == 0 or fails < max then
+    wait = math.floor(math.min(delay * 2 ^ (fails - 1), lock))
+end
+local till = now + wait
+local expire = math.max(now + window, till)
+redis.call('SET', KEYS[1], cjson.encode({target = ARGV[1], fails = fails, ['until'] = till, expire = expire}), 'EX', math.max(expire - now, 1))
+return fails
+"#;
+
+/// 登录失败 每次失败后的等待时间翻倍 达到次数上限后锁定 lock_time 秒
+pub(crate) async fn record_failure(ip: &str, username: &str) {
+    let limit = &get_config().login_limit;
+    let now = timestamp_10() as u64;
+    for (target, max) in targets(ip, username) {
+        if let Some(redis) = get_redis_conn().await {
+            let mut conn = redis.lock().await;
+            let res: RedisResult<u32> = redis::Script::new(RECORD_SCRIPT)
+                .key(format!("{}{}", KEY_PREFIX, target))
+                .arg(&target)
+                .arg(now)
+                .arg(limit.delay as u64)
+                .arg(limit.lock_time as u64)
+                .arg(max)
+                .arg(limit.window as u64)
+                .invoke_async(&mut *conn)
+                .await;
+            if res.is_ok() {
+                continue;
+            }
+        }
+        // 读和写在同一次加锁里完成
+        let mut memory = MEMORY.lock().await;
+        memory.retain(|_, f| f.expire > now);
+        let fail = memory.entry(target.clone()).or_default();
+        fail.target = target;
+        fail.fails += 1;
+        let wait = if max > 0 && fail.fails >= max {
+            limit.lock_time as u64
+        } else {
+            (limit.delay as u64)
+                .saturating_mul(1u64.checked_shl(fail.fails - 1).unwrap_or(u64::MAX))
+                .min(limit.lock_time as u64)
+        };
+        fail.until = now + wait;
+        fail.expire = (now + limit.window as u64).max(fail.until);
+    }
+}
+
+/// 登录成功清除用户名的失败记录 IP 的记录保留到过期
+pub(crate) async fn record_success(username: &str) {
+    clear_fails(&[user_target(username)]).await;
+}
+
+/// 所有未过期的失败记录 锁定中的在前
+pub(crate) async fn list_fails() -> Vec<LoginFail> {
+    let now = timestamp_10() as u64;
+    let mut rows: Vec<LoginFail> = MEMORY.lock().await.values().filter(|f| f.expire > now).cloned().collect();
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let mut keys: Vec<String> = Vec::new();
+        if let Ok(mut iter) = conn.scan_match::<_, String>(format!("{}*", KEY_PREFIX)).await {
+            while let Some(Ok(key)) = iter.next_item().await {
+                keys.push(key);
+            }
+        }
+        for key in keys {
+            let value: Option<String> = conn.get(&key).await.unwrap_or(None);
+            if let Some(fail) = value.and_then(|v| serde_json::from_str::<LoginFail>(&v).ok()) {
+                rows.push(fail);
+            }
+        }
+    }
+    rows.sort_by_key(|f| std::cmp::Reverse((f.until > now, f.until, f.fails)));
+    rows
+}
+
+/// 清除失败记录 解除锁定
+pub(crate) async fn clear_fails(targets: &[String]) {
+    {
+        let mut memory = MEMORY.lock().await;
+        for target in targets {
+            memory.remove(target);
+        }
+    }
+    let keys: Vec<String> = targets.iter().map(|t| format!("{}{}", KEY_PREFIX, t)).collect();
+    if keys.is_empty() {
+        return;
+    }
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let _: RedisResult<()> = conn.del(keys).await;
+    }
+}
+
+/// 先查 Redis Redis 不可用或出错时查内存
+async fn get_fail(target: &str) -> Option<LoginFail> {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<Option<String>> = conn.get(format!("{}{}", KEY_PREFIX, target)).await;
+        if let Ok(value) = res {
+            return value.and_then(|v| serde_json::from_str(&v).ok());
+        }
+    }
+    let now = timestamp_10() as u64;
+    MEMORY.lock().await.get(target).filter(|f| f.expire > now).cloned()
+}
+
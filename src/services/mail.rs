@@ -0,0 +1,241 @@
+use std::time::Duration;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_util::future::BoxFuture;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsConnector};
+use uuid::Uuid;
+use crate::utils::conf::get_config;
+
+/// SMTP 连接和每条命令的超时
+const SMTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("邮件配置错误：{0}")]
+    Config(String),
+    #[error("网络错误：{0}")]
+    Io(#[from] std::io::Error),
+    #[error("TLS错误：{0}")]
+    Tls(#[from] native_tls::Error),
+    #[error("SMTP服务器返回：{0}")]
+    Smtp(String),
+    #[error("SMTP服务器响应超时")]
+    Timeout,
+}
+
+/// 一封纯文本邮件
+#[derive(Debug, Clone)]
+pub(crate) struct MailMessage {
+    pub(crate) to: String,
+    pub(crate) subject: String,
+    pub(crate) body: String,
+}
+
+impl MailMessage {
+    /// 完整的邮件内容 头部用 RFC 2047 编码 正文 base64
+    fn to_rfc5322(&self, from: &str, from_name: &str) -> String {
+        let body = BASE64
+            .encode(self.body.replace("\r\n", "\n").replace('\n', "\r\n"))
+            .as_bytes()
+            .chunks(76)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        let domain = from.rsplit('@').next().unwrap_or("localhost");
+        format!(
+            "From: {} <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+            encode_header(from_name),
+            from,
+            self.to,
+            encode_header(&self.subject),
+            chrono::Local::now().to_rfc2822(),
+            Uuid::new_v4().to_simple(),
+            domain,
+            body
+        )
+    }
+}
+
+/// 非 ASCII 的头部用 =?UTF-8?B?...?= 编码
+fn encode_header(value: &str) -> String {
+    let value = strip_crlf(value);
+    if value.is_ascii() {
+        return value;
+    }
+    format!("=?UTF-8?B?{}?=", BASE64.encode(value))
+}
+
+/// 去掉换行 防止头部注入
+fn strip_crlf(value: &str) -> String {
+    value.chars().filter(|c| *c != '\r' && *c != '\n').collect()
+}
+
+/// 发信方式 由 mail.driver 选择
+pub(crate) trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, mail: &'a MailMessage) -> BoxFuture<'a, Result<(), MailError>>;
+}
+
+/// 通过 SMTP 服务器发送
+pub(crate) struct SmtpMailer;
+
+/// 保存为 .eml 文件 本地开发时用邮件客户端打开即可
+pub(crate) struct FileMailer;
+
+/// 只打印到控制台
+pub(crate) struct LogMailer;
+
+impl Mailer for SmtpMailer {
+    fn send<'a>(&'a self, mail: &'a MailMessage) -> BoxFuture<'a, Result<(), MailError>> {
+        Box::pin(async move {
+            tokio::time::timeout(SMTP_TIMEOUT * 4, smtp_send(mail))
+                .await
+                .map_err(|_| MailError::Timeout)?
+        })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send<'a>(&'a self, mail: &'a MailMessage) -> BoxFuture<'a, Result<(), MailError>> {
+        Box::pin(async move {
+            let conf = &get_config().mail;
+            tokio::fs::create_dir_all(&conf.dir).await?;
+            let path = format!(
+                "{}/{}-{}.eml",
+                conf.dir.trim_end_matches('/'),
+                chrono::Local::now().format("%Y%m%d%H%M%S"),
+                Uuid::new_v4().to_simple()
+            );
+            tokio::fs::write(&path, mail.to_rfc5322(&sender(), &sender_name())).await?;
+            println!("邮件已保存：{} -> {}", mail.to, path);
+            Ok(())
+        })
+    }
+}
+
+impl Mailer for LogMailer {
+    fn send<'a>(&'a self, mail: &'a MailMessage) -> BoxFuture<'a, Result<(), MailError>> {
+        Box::pin(async move {
+            println!("邮件 To: {}\nSubject: {}\n{}", mail.to, mail.subject, mail.body);
+            Ok(())
+        })
+    }
+}
+
+/// 按配置取发信方式 未知的 driver 当作 log
+pub(crate) fn mailer() -> Box<dyn Mailer> {
+    match get_config().mail.driver.as_str() {
+        "smtp" => Box::new(SmtpMailer),
+        "file" => Box::new(FileMailer),
+        _ => Box::new(LogMailer),
+    }
+}
+
+/// 发送邮件
+pub(crate) async fn send_mail(to: &str, subject: &str, body: &str) -> Result<(), MailError> {
+    let mail = MailMessage {
+        to: strip_crlf(to),
+        subject: subject.to_string(),
+        body: body.to_string(),
+    };
+    mailer().send(&mail).await
+}
+
+fn sender() -> String {
+    let conf = &get_config().mail;
+    if !conf.from.is_empty() {
+        return strip_crlf(&conf.from);
+    }
+    format!("{}@localhost", if conf.username.is_empty() { "noreply" } else { conf.username.as_str() })
+}
+
+fn sender_name() -> String {
+    let conf = &get_config().mail;
+    if conf.from_name.is_empty() {
+        get_config().site_name.clone()
+    } else {
+        conf.from_name.clone()
+    }
+}
+
+trait SmtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SmtpStream for T {}
+
+type SmtpConn = BufReader<Box<dyn SmtpStream>>;
+
+/// 读一条响应 多行响应读到第4个字符为空格的行为止 返回状态码和全文
+async fn read_reply(conn: &mut SmtpConn) -> Result<(u16, String), MailError> {
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        let n = tokio::time::timeout(SMTP_TIMEOUT, conn.read_line(&mut line))
+            .await
+            .map_err(|_| MailError::Timeout)??;
+        if n == 0 {
+            return Err(MailError::Smtp("连接已关闭".to_string()));
+        }
+        text.push_str(&line);
+        if line.len() < 4 || line.as_bytes()[3] != b'-' {
+            break;
+        }
+    }
+    let code = text.get(..3).and_then(|c| c.parse().ok()).unwrap_or(0);
+    Ok((code, text.trim_end().to_string()))
+}
+
+/// 发一条命令 状态码不是 expect 时报错
+async fn command(conn: &mut SmtpConn, cmd: &str, expect: u16) -> Result<String, MailError> {
+    conn.write_all(format!("{}\r\n", cmd).as_bytes()).await?;
+    conn.flush().await?;
+    let (code, text) = read_reply(conn).await?;
+    if code != expect {
+        return Err(MailError::Smtp(text));
+    }
+    Ok(text)
+}
+
+async fn tls_wrap(stream: Box<dyn SmtpStream>, host: &str) -> Result<Box<dyn SmtpStream>, MailError> {
+    let connector = TlsConnector::from(native_tls::TlsConnector::new()?);
+    Ok(Box::new(connector.connect(host, stream).await?))
+}
+
+async fn smtp_send(mail: &MailMessage) -> Result<(), MailError> {
+    let conf = &get_config().mail;
+    if conf.host.is_empty() {
+        return Err(MailError::Config("未设置 SMTP 服务器".to_string()));
+    }
+    let from = sender();
+    let tcp = tokio::time::timeout(SMTP_TIMEOUT, TcpStream::connect((conf.host.as_str(), conf.port)))
+        .await
+        .map_err(|_| MailError::Timeout)??;
+    let mut stream: Box<dyn SmtpStream> = Box::new(tcp);
+    if conf.security == "tls" {
+        stream = tls_wrap(stream, &conf.host).await?;
+    }
+    let mut conn = BufReader::new(stream);
+    let (code, text) = read_reply(&mut conn).await?;
+    if code != 220 {
+        return Err(MailError::Smtp(text));
+    }
+    let ehlo = format!("EHLO {}", from.rsplit('@').next().unwrap_or("localhost"));
+    command(&mut conn, &ehlo, 250).await?;
+    if conf.security == "starttls" {
+        command(&mut conn, "STARTTLS", 220).await?;
+        conn = BufReader::new(tls_wrap(conn.into_inner(), &conf.host).await?);
+        command(&mut conn, &ehlo, 250).await?;
+    }
+    if !conf.username.is_empty() {
+        let auth = BASE64.encode(format!("\0{}\0{}", conf.username, conf.password));
+        command(&mut conn, &format!("AUTH PLAIN {}", auth), 235).await?;
+    }
+    command(&mut conn, &format!("MAIL FROM:<{}>", from), 250).await?;
+    command(&mut conn, &format!("RCPT TO:<{}>", mail.to), 250).await?;
+    command(&mut conn, "DATA", 354).await?;
+    // 正文是 base64 不会出现单独一行的 . 不需要转义
+    let data = format!("{}.", mail.to_rfc5322(&from, &sender_name()));
+    command(&mut conn, &data, 250).await?;
+    let _ = command(&mut conn, "QUIT", 221).await;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::redis::store::{set_value, set_value_nx, take_value};
+
+const KEY_PREFIX: &str = "novel:mail_token:";
+const COOLDOWN_PREFIX: &str = "novel:mail_cooldown:";
+
+/// 签名密钥 取 .env 的 MAIL_SECRET 未设置时每次启动随机生成 重启后旧链接失效
+static SECRET: Lazy<Vec<u8>> = Lazy::new(|| match std::env::var("MAIL_SECRET") {
+    Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+    _ => {
+        eprintln!("未设置 MAIL_SECRET 邮件链接在重启后失效");
+        let mut bytes = vec![0u8; 32];
+        rand::rng().fill(&mut bytes[..]);
+        bytes
+    }
+});
+
+/// 邮件链接的用途 不同用途的 token 不能混用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TokenPurpose {
+    /// 找回密码
+    Reset,
+    /// 验证邮箱
+    Verify,
+    /// 修改邮箱 发到新邮箱确认
+    ChangeEmail,
+}
+
+impl TokenPurpose {
+    fn code(self) -> &'static str {
+        match self {
+            Self::Reset => "r",
+            Self::Verify => "v",
+            Self::ChangeEmail => "c",
+        }
+    }
+}
+
+/// token 对应的数据 只存在服务端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct MailToken {
+    pub(crate) userid: u64,
+    /// 验证或改成的邮箱
+    pub(crate) email: String,
+}
+
+fn sign(purpose: TokenPurpose, nonce: &str, expire: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&SECRET).expect("HMAC 可以使用任意长度的密钥");
+    mac.update(format!("{}|{}|{}", purpose.code(), nonce, expire).as_bytes());
+    mac
+}
+
+fn token_key(purpose: TokenPurpose, nonce: &str) -> String {
+    format!("{}{}:{}", KEY_PREFIX, purpose.code(), nonce)
+}
+
+/// 生成 token 格式为 nonce.过期时间.签名 数据存 Redis 有效期为 mail.token_ttl
+pub(crate) async fn create_token(purpose: TokenPurpose, data: &MailToken) -> String {
+    let ttl = get_config().mail.token_ttl.max(60) as u64;
+    let expire = timestamp_10() as u64 + ttl;
+    let mut bytes = [0u8; 16];
+    rand::rng().fill(&mut bytes);
+    let nonce = hex::encode(bytes);
+    let sig = hex::encode(sign(purpose, &nonce, expire).finalize().into_bytes());
+    let value = serde_json::to_string(data).unwrap_or_default();
+    set_value(&token_key(purpose, &nonce), &value, ttl).await;
+    format!("{}.{}.{}", nonce, expire, sig)
+}
+
+/// 校验签名和有效期后取出数据 token 只能用一次
+pub(crate) async fn take_token(purpose: TokenPurpose, token: &str) -> Option<MailToken> {
+    let mut parts = token.trim().splitn(3, '.');
+    let (nonce, expire, sig) = (parts.next()?, parts.next()?, parts.next()?);
+    if nonce.len() != 32 || !nonce.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let expire: u64 = expire.parse().ok()?;
+    if expire <= timestamp_10() as u64 {
+        return None;
+    }
+    sign(purpose, nonce, expire).verify_slice(&hex::decode(sig).ok()?).ok()?;
+    let value = take_value(&token_key(purpose, nonce)).await?;
+    serde_json::from_str(&value).ok()
+}
+
+/// 同一用户同一用途的邮件 seconds 秒内只发一次 可以发送时返回 true
+pub(crate) async fn try_cooldown(purpose: TokenPurpose, user_id: u64, seconds: u64) -> bool {
+    let key = format!("{}{}:{}", COOLDOWN_PREFIX, purpose.code(), user_id);
+    set_value_nx(&key, "1", seconds).await
+}
@@ -1,4 +1,23 @@
 pub(crate) mod novel;
 pub(crate) mod json;
 pub(crate) mod user;
-pub(crate) mod lang_tail;
\ No newline at end of file
+pub(crate) mod lang_tail;
+pub(crate) mod epub;
+pub(crate) mod vote;
+pub(crate) mod report;
+pub(crate) mod search;
+pub(crate) mod visit;
+pub(crate) mod sitemap;
+pub(crate) mod feed;
+pub(crate) mod history;
+pub(crate) mod session;
+pub(crate) mod password;
+pub(crate) mod login_limit;
+pub(crate) mod captcha;
+pub(crate) mod mail;
+pub(crate) mod mail_token;
+pub(crate) mod email;
+pub(crate) mod account;
+pub(crate) mod admin;
+pub(crate) mod admin_novel;
+pub(crate) mod admin_chapter;
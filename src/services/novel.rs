@@ -5,18 +5,26 @@ use axum::http::{HeaderMap, Uri};
 use axum::http::header::HOST;
 use encoding_rs::{Encoding, GBK};
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use sea_orm::{DbErr, FromQueryResult, Statement, Value, Values};
+use url::Url;
 use crate::handlers::define::NOVEL_CHAPTER_FILED;
+use crate::models::config::Rank;
 use crate::models::novel::{Novel, NovelChapter};
 use crate::utils;
 use crate::utils::conf::get_config;
 use crate::utils::db::conn::get_db_conn_ref;
-use crate::utils::redis::conn::{get_cache_rows};
+use crate::services::epub;
+use crate::utils::redis::conn::{cache_del, cache_key, get_cache_count, get_cache_rows};
 use crate::utils::text::time_to_cn;
 
 const HTTP_PREFIX: &str = "http://";
 const HTTPS_PREFIX: &str = "https://";
 
+static BR_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)<br\s+[^>]*?/?>").expect("正则编译失败"));
+
 // 通用标签赋予
 pub(crate) fn process_tera_tag(
     headers: &HeaderMap,
@@ -53,12 +61,8 @@ pub(crate) async fn get_novel_info(
     cache: u32,
     source_id: u64,
 ) -> Vec<Novel> {
-    get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND articleid = ? LIMIT 1;",filed=get_config().get_field(),table=get_config().prefix,where=get_config().get_where()),
-        url,
-        cache as u64,
-        Some(Values(vec![Value::BigUnsigned(Some(source_id))])),
-    ).await
+    let (sql, value) = novel_info_sql(source_id);
+    get_cache_rows(sql, url, cache as u64, value).await
 }
 
 pub(crate) async fn get_chapter_rows(
@@ -66,12 +70,72 @@ pub(crate) async fn get_chapter_rows(
     cache: u32,
     source_id: u64,
 ) -> Vec<NovelChapter> {
-    utils::redis::conn::get_chapter_rows(
-        format!("SELECT {filed} FROM {table} WHERE articleid = ? ORDER BY chapterid ASC;",filed=NOVEL_CHAPTER_FILED,table=get_config().get_chapter_table(source_id)),
-        url,
-        cache as u64,
+    let (sql, value) = chapter_rows_sql(source_id);
+    utils::redis::conn::get_chapter_rows(sql, url, cache as u64, value).await
+}
+
+fn novel_info_sql(source_id: u64) -> (String, Option<Values>) {
+    (
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND articleid = ? LIMIT 1;",filed=get_config().get_field(),table=get_config().prefix,where=get_config().get_where()),
         Some(Values(vec![Value::BigUnsigned(Some(source_id))])),
-    ).await
+    )
+}
+
+fn chapter_rows_sql(source_id: u64) -> (String, Option<Values>) {
+    (
+        format!("SELECT {filed} FROM {table} WHERE articleid = ? ORDER BY chapterorder ASC, chapterid ASC;",filed=NOVEL_CHAPTER_FILED,table=get_config().get_chapter_table(source_id)),
+        Some(Values(vec![Value::BigUnsigned(Some(source_id))])),
+    )
+}
+
+/// 最近更新 首页和全站 Atom 共用 不含隐藏的小说
+pub(crate) fn lastupdate_sql() -> String {
+    format!("SELECT {filed} FROM {table}article_article WHERE {where} ORDER BY lastupdate DESC LIMIT 30",filed=get_config().get_field(),table=get_config().prefix,where=get_config().get_where())
+}
+
+/// 清除小说信息和章节列表的缓存 以及已生成的EPUB
+///
+/// 缓存键包含域名 需要传入产生缓存时的域名
+async fn purge_novel_cache(url: &str, source_id: u64) {
+    let (info_sql, info_value) = novel_info_sql(source_id);
+    let (chapter_sql, chapter_value) = chapter_rows_sql(source_id);
+    cache_del(&[
+        cache_key("rows", url, &info_sql, &info_value),
+        cache_key("chapters", url, &chapter_sql, &chapter_value),
+    ]).await;
+    epub::remove_epub(source_id);
+}
+
+/// 站点的全部域名 site_url 的域名加上 domains 格式与请求头 Host 相同
+fn site_hosts() -> Vec<String> {
+    let mut hosts: Vec<String> = vec![];
+    if let Ok(site_url) = Url::parse(get_config().site_url.trim())
+        && let Some(host) = site_url.host_str()
+    {
+        hosts.push(match site_url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        });
+    }
+    for host in get_config().domains.iter() {
+        let host = host.trim().to_ascii_lowercase();
+        if !host.is_empty() && !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// 后台修改小说后清除 site_url 和 domains 各域名下的缓存 分类、排行等列表的缓存不清除 到期后更新
+pub(crate) async fn purge_novel_cache_all(source_id: u64) {
+    for host in site_hosts() {
+        purge_novel_cache(&host, source_id).await;
+    }
+    let site_url = get_config().site_url.trim().trim_end_matches('/').to_string();
+    if !site_url.is_empty() {
+        cache_del(&[cache_key("feed", &site_url, "novel", &Some(Values(vec![source_id.into()])))]).await;
+    }
+    epub::remove_epub(source_id);
 }
 
 pub(crate) async fn read_file(path_or_url: &str) -> String {
@@ -82,13 +146,148 @@ pub(crate) async fn read_file(path_or_url: &str) -> String {
     read_txt_to_utf8(s).unwrap_or(String::new())
 }
 
+/// 章节TXT路径 传入的是未混淆的源ID
+pub(crate) fn chapter_txt_url(source_id: u64, source_chapter_id: u64) -> String {
+    format!(
+        "{}/{}/{}/{}.txt",
+        get_config().txt_url,
+        source_id / 1000,
+        source_id,
+        source_chapter_id
+    )
+}
+
+/// 读取章节正文 并把<br>标签替换为换行 读取失败返回空字符串
+pub(crate) async fn get_chapter_content(source_id: u64, source_chapter_id: u64) -> String {
+    let content = read_file(&chapter_txt_url(source_id, source_chapter_id)).await;
+    if content.is_empty() {
+        return content;
+    }
+    BR_REGEX.replace_all(&content, "\n").to_string()
+}
+
+/// 章节正文纯文本 每段一行 不分页 供接口使用
+pub(crate) async fn get_chapter_text(source_id: u64, source_chapter_id: u64) -> String {
+    let content = get_chapter_content(source_id, source_chapter_id).await;
+    let text = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if get_config().is_filter {
+        return utils::conf::multi_replace(&text);
+    }
+    text
+}
+
+/// 分类列表 sort_index 为 sort_arr 的下标 返回 (最大页码, 当前页)
+///
+/// 页码超出时返回空列表
+pub(crate) async fn get_sort_page(url: &str, sort_index: usize, page: u64) -> (u64, Vec<Novel>) {
+    let sort_value = Value::TinyInt(Some((sort_index + 1) as i8));
+    let count = get_cache_count(
+        format!("SELECT COUNT(*) AS cnt FROM {table}article_article WHERE {where} AND sortid = ?;", table = get_config().prefix, where = get_config().get_where()),
+        url,
+        get_config().cache.sort as u64,
+        Some(Values(vec![sort_value.clone()])),
+    ).await;
+    let mut max_page = count.div_ceil(get_config().category_per_page as u64).max(1);
+    if get_config().category_max_page > 0 && max_page > get_config().category_max_page {    // 如果启用了访问深度 则此处生效
+        max_page = get_config().category_max_page;
+    }
+    if page == 0 || page > max_page {
+        return (max_page, vec![]);
+    }
+    let offset = (page - 1).saturating_mul(get_config().category_per_page as u64);
+    let rows = get_cache_rows(
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND sortid = ? ORDER BY lastupdate DESC LIMIT {limit} OFFSET ?;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), limit = get_config().category_per_page),
+        url,
+        get_config().cache.sort as u64,
+        Some(Values(vec![sort_value, offset.into()])),
+    ).await;
+    (max_page, rows)
+}
+
+/// 完本列表 sort_index 为 None 时不按分类筛选 返回 (最大页码, 当前页)
+pub(crate) async fn get_complete_page(url: &str, sort_index: Option<usize>, page: u64) -> (u64, Vec<Novel>) {
+    let (sort_where, sort_values) = match sort_index {
+        Some(i) => ("AND sortid = ?", vec![Value::TinyInt(Some((i + 1) as i8))]),
+        None => ("", vec![]),
+    };
+    let count = get_cache_count(
+        format!("SELECT COUNT(*) AS cnt FROM {table}article_article WHERE {where} AND fullflag = 1 {sort_where};", table = get_config().prefix, where = get_config().get_where(), sort_where = sort_where),
+        url,
+        get_config().cache.sort as u64,
+        Some(Values(sort_values.clone())),
+    ).await;
+    let mut max_page = count.div_ceil(get_config().category_per_page as u64).max(1);
+    if get_config().category_max_page > 0 && max_page > get_config().category_max_page {    // 与分类页共用访问深度
+        max_page = get_config().category_max_page;
+    }
+    if page == 0 || page > max_page {
+        return (max_page, vec![]);
+    }
+    let mut row_values = sort_values;
+    row_values.push(((page - 1).saturating_mul(get_config().category_per_page as u64)).into());
+    let rows = get_cache_rows(
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND fullflag = 1 {sort_where} ORDER BY lastupdate DESC LIMIT {limit} OFFSET ?;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), sort_where = sort_where, limit = get_config().category_per_page),
+        url,
+        get_config().cache.sort as u64,
+        Some(Values(row_values)),
+    ).await;
+    (max_page, rows)
+}
+
+/// 排行列表 sort_index 为 None 时不按分类筛选 返回 (最大页码, 当前页)
+///
+/// 排序字段只能来自白名单 不能作为参数绑定 否则 MySQL 会按常量排序
+pub(crate) async fn get_rank_page(url: &str, rank: &Rank, sort_index: Option<usize>, page: u64) -> (u64, Vec<Novel>) {
+    let cache_time = if rank.cache > 0 { rank.cache } else { get_config().cache.rank } as u64;
+    let (sort_where, sort_values) = match sort_index {
+        Some(i) => ("AND sortid = ?", vec![Value::TinyInt(Some((i + 1) as i8))]),
+        None => ("", vec![]),
+    };
+    let per_page = get_config().category_per_page as u64;
+    let count = get_cache_count(
+        format!("SELECT COUNT(*) AS cnt FROM {table}article_article WHERE {where} {sort_where};", table = get_config().prefix, where = get_config().get_where(), sort_where = sort_where),
+        url,
+        cache_time,
+        Some(Values(sort_values.clone())),
+    ).await;
+    let mut max_page = count.div_ceil(per_page).max(1);
+    if get_config().category_max_page > 0 && max_page > get_config().category_max_page {    // 与分类页共用访问深度
+        max_page = get_config().category_max_page;
+    }
+    if page == 0 || page > max_page {
+        return (max_page, vec![]);
+    }
+    let mut row_values = sort_values;
+    row_values.push(((page - 1).saturating_mul(per_page)).into());
+    let rows = get_cache_rows(
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} {sort_where} ORDER BY {column} DESC LIMIT {limit} OFFSET ?;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), sort_where = sort_where, column = rank.code, limit = per_page),
+        url,
+        cache_time,
+        Some(Values(row_values)),
+    ).await;
+    (max_page, rows)
+}
+
 pub fn read_txt_to_utf8<P: AsRef<Path>>(path: P) -> io::Result<String> {
     let bytes = std::fs::read(path)?;
     // if let Ok(s) = std::str::from_utf8(&bytes) {
     //     // let s = s.strip_prefix('\u{feff}').unwrap_or(s);
     //     return Ok(s.to_owned());
     // }
-    decode_bytes_with_encoding(&bytes, GBK)
+    decode_bytes_with_encoding(&bytes, txt_encoding(&bytes))
+}
+
+/// 本地txt的编码 带BOM时按BOM 否则按 txt_encoding 配置 无法识别的配置当作GBK
+pub(crate) fn txt_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    Encoding::for_label(get_config().txt_encoding.trim().as_bytes()).unwrap_or(GBK)
 }
 
 fn decode_bytes_with_encoding(bytes: &[u8], encoding: &'static Encoding) -> io::Result<String> {
@@ -126,7 +325,7 @@ pub(crate) async fn common_novel_random(
     cache: u64
 ) -> Vec<Novel> {
     get_cache_rows(
-        format!("SELECT {filed} FROM {table}article_article WHERE articleid >= (SELECT FLOOR(RAND() * (SELECT MAX(articleid) FROM {table}article_article))) ORDER BY lastupdate DESC LIMIT {limit}", filed = get_config().get_field(), table = get_config().prefix, limit = limit),
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND articleid >= (SELECT FLOOR(RAND() * (SELECT MAX(articleid) FROM {table}article_article))) ORDER BY lastupdate DESC LIMIT {limit}", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), limit = limit),
         url,
         cache,
         None,
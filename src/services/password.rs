@@ -0,0 +1,127 @@
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use rand::Rng;
+use sea_orm::{DbErr, FromQueryResult, Values};
+use uuid::Uuid;
+use crate::models::user::User;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, get_one_as};
+
+/// 只用 Argon2 的用户 system_users.pass 写入这个值 任何 MD5 都不会等于它
+pub(crate) const UNUSABLE_PASS: &str = "*";
+
+#[derive(Debug, FromQueryResult)]
+struct PasswordRow {
+    hash: String,
+}
+
+/// 杰奇的密码格式 sys_ver < 2.0 为 md5(pass) 否则为 md5(md5(pass) + salt)
+pub(crate) fn jieqi_hash(password: &str, salt: &str) -> String {
+    let pass = format!("{:x}", md5::compute(password.as_bytes()));
+    if get_config().sys_ver < 2.0 {
+        return pass;
+    }
+    format!("{:x}", md5::compute(format!("{}{}", pass, salt).as_bytes()))
+}
+
+/// 杰奇 2.x 的 16 位 salt
+pub(crate) fn jieqi_salt() -> String {
+    let md5_hex = format!("{:x}", md5::compute(Uuid::new_v4().to_string().as_bytes()));
+    md5_hex[md5_hex.len() - 16..].to_string()
+}
+
+/// system_users.pass 是否为杰奇的 MD5 格式
+pub(crate) fn is_jieqi_hash(pass: &str) -> bool {
+    pass.len() == 32 && pass.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Argon2id 哈希 PHC 字符串格式 计算较慢 放到阻塞线程里
+pub(crate) async fn hash_password(password: &str) -> Option<String> {
+    let password = password.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let mut bytes = [0u8; 16];
+        rand::rng().fill(&mut bytes);
+        let salt = SaltString::encode_b64(&bytes).ok()?;
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .inspect_err(|e| eprintln!("hash_password error: {e:?}"))
+            .ok()
+            .map(|h| h.to_string())
+    }).await.ok()?
+}
+
+async fn verify_argon2(hash: &str, password: &str) -> bool {
+    let hash = hash.to_owned();
+    let password = password.to_owned();
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    }).await.unwrap_or(false)
+}
+
+async fn get_password_hash(user_id: u64) -> Result<Option<String>, DbErr> {
+    let sql = format!("SELECT hash FROM {}user_password WHERE userid = ? LIMIT 1", get_config().prefix);
+    Ok(get_one_as::<PasswordRow>(&sql, Some(Values(vec![user_id.into()]))).await?.map(|r| r.hash))
+}
+
+/// 写入 Argon2 哈希 已存在则覆盖
+pub(crate) async fn set_password_hash(user_id: u64, hash: &str) -> Result<(), DbErr> {
+    let sql = format!(
+        "INSERT INTO {}user_password (userid, hash, updated) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE hash = VALUES(hash), updated = VALUES(updated)",
+        get_config().prefix
+    );
+    exec_sql(&sql, Some(Values(vec![user_id.into(), hash.into(), timestamp_10().into()]))).await?;
+    Ok(())
+}
+
+/// 校验密码 有 Argon2 哈希时优先使用
+///
+/// 没有 Argon2 哈希的老用户按杰奇格式校验 通过后补写 Argon2 哈希 system_users.pass 默认保持不变
+/// 开启 jieqi_password 时密码可能在 PHP 杰奇里改过 Argon2 不通过还会再按杰奇格式校验一次
+pub(crate) async fn verify_user_password(user: &User, password: &str) -> Result<bool, DbErr> {
+    let stored = get_password_hash(user.uid).await?;
+    if let Some(hash) = stored.as_deref()
+        && verify_argon2(hash, password).await
+    {
+        return Ok(true);
+    }
+    if stored.is_some() && !get_config().jieqi_password {
+        return Ok(false);
+    }
+    if !is_jieqi_hash(&user.pass) || user.pass != jieqi_hash(password, &user.salt) {
+        return Ok(false);
+    }
+    let Some(hash) = hash_password(password).await else {
+        return Ok(true);
+    };
+    if let Err(e) = set_password_hash(user.uid, &hash).await {
+        eprintln!("rehash password error: {e:?}");
+        return Ok(true);
+    }
+    // 需要开启 jieqi_password_clear 才清掉 MD5 旧版 cookie 也随之失效
+    if !get_config().jieqi_password && get_config().jieqi_password_clear {
+        let sql = format!("UPDATE {}system_users SET pass = ? WHERE uid = ?", get_config().prefix);
+        if let Err(e) = exec_sql(&sql, Some(Values(vec![UNUSABLE_PASS.into(), user.uid.into()]))).await {
+            eprintln!("clear jieqi password error: {e:?}");
+        }
+    }
+    Ok(true)
+}
+
+/// 设置新密码 用于找回密码和修改密码
+///
+/// 开启 jieqi_password 时同时更新杰奇格式的密码 否则 system_users.pass 改为 * 旧的 MD5 和旧版 cookie 都不再有效
+pub(crate) async fn change_password(user: &User, password: &str) -> Result<(), DbErr> {
+    let hash = hash_password(password).await.ok_or_else(|| DbErr::Custom("hash_password failed".to_string()))?;
+    set_password_hash(user.uid, &hash).await?;
+    let pass = if get_config().jieqi_password {
+        jieqi_hash(password, &user.salt)
+    } else {
+        UNUSABLE_PASS.to_string()
+    };
+    let sql = format!("UPDATE {}system_users SET pass = ? WHERE uid = ?", get_config().prefix);
+    exec_sql(&sql, Some(Values(vec![pass.into(), user.uid.into()]))).await?;
+    Ok(())
+}
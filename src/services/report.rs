@@ -0,0 +1,156 @@
+use redis::AsyncCommands;
+use sea_orm::{DbErr, Value, Values};
+use thiserror::Error;
+use crate::models::report::{ChapterReport, REPORT_DISMISSED, REPORT_PENDING, REPORT_RESOLVED};
+use crate::services::novel::purge_novel_cache_all;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_all_as, query_count};
+use crate::utils::redis::conn::get_redis_conn;
+
+/// 报错原因最多保留的字符数 与表结构一致
+const REASON_MAX_CHARS: usize = 255;
+
+#[derive(Error, Debug)]
+pub enum ReportError {
+    #[error("管理员已关闭章节报错功能")]
+    Disabled,
+    #[error("报错间隔 {0} 秒")]
+    TooFrequent(u32),
+    #[error("数据库错误：{0}")]
+    DbErr(#[from] DbErr),
+}
+
+/// 新增的报错内容 ID 均为数据库中的原始ID
+pub(crate) struct NewReport<'a> {
+    pub(crate) articleid: u64,
+    pub(crate) articlename: &'a str,
+    pub(crate) chapterid: u64,
+    pub(crate) chaptername: &'a str,
+    pub(crate) reason: &'a str,
+    pub(crate) fingerprint: &'a str,
+    pub(crate) host: &'a str,
+}
+
+/// 记录一条章节报错
+///
+/// cookie 之外再用客户端指纹在 Redis 里做一次间隔限制 防止不带 cookie 的刷报错
+pub(crate) async fn add_report(report: NewReport<'_>) -> Result<(), ReportError> {
+    if !get_config().is_report {
+        return Err(ReportError::Disabled);
+    }
+    let report_time = get_config().report_time;
+    if report_time > 0 && !try_lock_client(report.fingerprint, report_time).await {
+        return Err(ReportError::TooFrequent(report_time));
+    }
+    let reason: String = report.reason.trim().chars().take(REASON_MAX_CHARS).collect();
+    let sql = format!(
+        "INSERT INTO {}article_report (articleid, articlename, chapterid, chaptername, reason, fingerprint, host, reporttime, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        get_config().prefix
+    );
+    exec_sql(
+        sql.as_str(),
+        Some(Values(vec![
+            report.articleid.into(),
+            report.articlename.into(),
+            report.chapterid.into(),
+            report.chaptername.into(),
+            reason.into(),
+            report.fingerprint.into(),
+            report.host.into(),
+            timestamp_10().into(),
+            REPORT_PENDING.into(),
+        ])),
+    ).await?;
+    Ok(())
+}
+
+/// 按状态分页取报错列表 返回 (总数, 当前页)
+pub(crate) async fn get_report_list(status: u8, page: u64, per_page: u64) -> Result<(u64, Vec<ChapterReport>), DbErr> {
+    let table = format!("{}article_report", get_config().prefix);
+    let count = query_count(
+        format!("SELECT COUNT(*) AS cnt FROM {} WHERE status = ?", table).as_str(),
+        Some(Values(vec![status.into()])),
+    ).await?;
+    let offset = page.saturating_sub(1).saturating_mul(per_page);
+    let mut rows: Vec<ChapterReport> = query_all_as(
+        format!("SELECT reportid, articleid, articlename, chapterid, chaptername, reason, fingerprint, host, reporttime, status FROM {} WHERE status = ? ORDER BY reportid DESC LIMIT ? OFFSET ?", table).as_str(),
+        Some(Values(vec![status.into(), per_page.into(), offset.into()])),
+    ).await?;
+    for row in rows.iter_mut() {
+        row.read_url = get_config().read_url(
+            get_config().new_id(row.articleid),
+            get_config().new_id(row.chapterid),
+            1,
+        );
+    }
+    Ok((count, rows))
+}
+
+/// 标记为已处理 同时清除对应小说的缓存 返回处理的条数
+pub(crate) async fn resolve_reports(ids: &[u64]) -> Result<u64, DbErr> {
+    let pending = get_pending_targets(ids).await?;
+    let n = set_report_status(ids, REPORT_RESOLVED).await?;
+    for articleid in pending {
+        purge_novel_cache_all(articleid).await;
+    }
+    Ok(n)
+}
+
+/// 批量忽略 返回处理的条数
+pub(crate) async fn dismiss_reports(ids: &[u64]) -> Result<u64, DbErr> {
+    set_report_status(ids, REPORT_DISMISSED).await
+}
+
+/// 取得待处理报错涉及的小说ID 已去重
+async fn get_pending_targets(ids: &[u64]) -> Result<Vec<u64>, DbErr> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let sql = format!(
+        "SELECT reportid, articleid, articlename, chapterid, chaptername, reason, fingerprint, host, reporttime, status FROM {}article_report WHERE status = ? AND reportid IN ({})",
+        get_config().prefix,
+        placeholders(ids.len())
+    );
+    let mut values: Vec<Value> = vec![REPORT_PENDING.into()];
+    values.extend(ids.iter().map(|id| Value::from(*id)));
+    let rows: Vec<ChapterReport> = query_all_as(sql.as_str(), Some(Values(values))).await?;
+    let mut targets: Vec<u64> = rows.into_iter().map(|r| r.articleid).collect();
+    targets.sort();
+    targets.dedup();
+    Ok(targets)
+}
+
+async fn set_report_status(ids: &[u64], status: u8) -> Result<u64, DbErr> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let sql = format!(
+        "UPDATE {}article_report SET status = ? WHERE status = ? AND reportid IN ({})",
+        get_config().prefix,
+        placeholders(ids.len())
+    );
+    let mut values: Vec<Value> = vec![status.into(), REPORT_PENDING.into()];
+    values.extend(ids.iter().map(|id| Value::from(*id)));
+    exec_sql(sql.as_str(), Some(Values(values))).await
+}
+
+/// 占用客户端的报错间隔 间隔内已报过错返回 false
+///
+/// Redis 不可用时只靠 cookie 限制 直接放行
+async fn try_lock_client(fingerprint: &str, report_time: u32) -> bool {
+    let Some(redis) = get_redis_conn().await else {
+        return true;
+    };
+    let key = format!("novel:report:{}", fingerprint);
+    let mut conn = redis.lock().await;
+    let opts = redis::SetOptions::default()
+        .conditional_set(redis::ExistenceCheck::NX)
+        .with_expiration(redis::SetExpiry::EX(report_time as u64));
+    let res: Result<Option<String>, _> = conn.set_options(&key, 1, opts).await;
+    !matches!(res, Ok(None))
+}
+
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use arc_swap::ArcSwap;
+use axum_extra::extract::CookieJar;
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use sea_orm::{DbErr, FromQueryResult, Value, Values};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::models::novel::Novel;
+use crate::services::captcha;
+use crate::services::user::timestamp_10;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_all_as, query_count};
+use crate::utils::redis::conn::{get_cache_count, get_cache_rows, get_redis_conn};
+
+/// 待写入数据库的搜索词 字段为 "1|关键字"(有结果) 或 "0|关键字"(无结果)
+const PENDING_KEY: &str = "novel:search:pending";
+/// 正在写入数据库的搜索词 写入失败会保留到下次重试
+const FLUSH_KEY: &str = "novel:search:flush";
+/// 写入数据库的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// 关键字最多保留的字符数 与表结构一致
+const KEYWORD_MAX_CHARS: usize = 60;
+/// 内存中保留的热门搜索数 hot_search 的 limit 不会超过这个值
+const HOT_SEARCH_MAX: u64 = 50;
+/// 每条 INSERT 语句写入的行数
+const BATCH_ROWS: usize = 200;
+
+/// 热门搜索 后台任务定时从数据库刷新 模板函数直接读取
+static HOT_SEARCH: Lazy<ArcSwap<Vec<SearchKeyword>>> = Lazy::new(|| ArcSwap::from_pointee(Vec::new()));
+
+#[derive(Debug, Clone, FromQueryResult, Serialize, Deserialize)]
+pub(crate) struct SearchKeyword {
+    pub(crate) keyword: String,
+    pub(crate) searchnum: u64,
+    pub(crate) hitnum: u64,
+    pub(crate) missnum: u64,
+    pub(crate) lasttime: u64,
+    #[sea_orm(skip)]
+    pub(crate) url: String,
+}
+
+/// 搜索间隔内保存上次搜索关键字摘要的 cookie
+pub(crate) const SEARCH_LAST_COOKIE: &str = "search_last_time";
+
+/// 搜索被拒绝的原因 搜索页、阅读书源和 OPDS 共用
+#[derive(Error, Debug)]
+pub(crate) enum SearchDenied {
+    #[error("管理员已关闭搜索功能")]
+    Closed,
+    #[error("关键字最少 {0} 个字符")]
+    TooShort(u8),
+    #[error("搜索间隔: {0} 秒")]
+    Delay(i32),
+    #[error("搜索太频繁，请稍后再试")]
+    TooFrequent,
+    #[error("验证码错误或已过期，请重新输入")]
+    CaptchaFailed,
+}
+
+/// 关键字的摘要 用于判断间隔内是否只是翻页
+pub(crate) fn keyword_hash(keyword: &str) -> String {
+    format!("{:x}", md5::compute(normalize_keyword(keyword)))
+}
+
+/// 搜索前的检查 所有搜索入口都要先调用
+///
+/// 搜索页传 jar 上次搜索取自 cookie 由搜索页自己写入 没有 jar 的入口按IP记录
+/// captcha 为表单提交的 (captcha_id, captcha) 没有验证码页面的入口超过次数后直接拒绝
+pub(crate) async fn check_search(
+    ip: &str,
+    keyword: &str,
+    page: u64,
+    jar: Option<&CookieJar>,
+    captcha: Option<(&str, Option<&str>)>,
+) -> Result<(), SearchDenied> {
+    let delay = get_config().search.delay;
+    if delay == -1 {
+        return Err(SearchDenied::Closed);
+    }
+    if keyword.graphemes(true).count() < get_config().search.min as usize {
+        return Err(SearchDenied::TooShort(get_config().search.min));
+    }
+    // 间隔内只允许翻页
+    let hash = keyword_hash(keyword);
+    let last = match jar {
+        Some(jar) => jar.get(SEARCH_LAST_COOKIE).map(|c| c.value().to_string()),
+        None => captcha::get_last_search(ip).await,
+    };
+    if last.is_some_and(|last| page == 1 || last != hash) {
+        return Err(SearchDenied::Delay(delay));
+    }
+    // 翻页不计数也不需要验证码
+    if page == 1 && get_config().captcha.search > 0 {
+        match (captcha, jar) {
+            (Some((id, input)), Some(jar)) => {
+                if !captcha::verify_form(jar, Some(id), input).await {
+                    return Err(SearchDenied::CaptchaFailed);
+                }
+                captcha::reset_search_count(ip).await;
+            }
+            _ if captcha::incr_search_count(ip).await > get_config().captcha.search => {
+                return Err(SearchDenied::TooFrequent);
+            }
+            _ => {}
+        }
+    }
+    if jar.is_none() && delay > 0 && page == 1 {
+        captcha::set_last_search(ip, &hash, delay as u64).await;
+    }
+    Ok(())
+}
+
+/// 统一关键字格式 去掉首尾空白 连续空白合并为一个空格 英文转小写
+pub(crate) fn normalize_keyword(keyword: &str) -> String {
+    keyword
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .chars()
+        .take(KEYWORD_MAX_CHARS)
+        .collect()
+}
+
+/// 记录一次搜索 只写 Redis 由后台任务批量写入数据库
+///
+/// Redis 不可用时不记录
+pub(crate) async fn record_keyword(keyword: &str, hit: bool) {
+    if !get_config().search.is_record {
+        return;
+    }
+    let keyword = normalize_keyword(keyword);
+    if keyword.is_empty() {
+        return;
+    }
+    let Some(redis) = get_redis_conn().await else {
+        return;
+    };
+    let field = format!("{}|{}", if hit { 1 } else { 0 }, keyword);
+    let mut conn = redis.lock().await;
+    let _: Result<i64, _> = conn.hincr(PENDING_KEY, field, 1).await;
+}
+
+/// 从缓存中取热门搜索 只包含有搜索结果的关键字
+pub(crate) fn get_hot_search(limit: usize) -> Vec<SearchKeyword> {
+    HOT_SEARCH.load().iter().take(limit).cloned().collect()
+}
+
+/// 后台任务 定时把 Redis 中的搜索词写入数据库并刷新热门搜索
+pub(crate) async fn record_task() {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = flush_keywords().await {
+            eprintln!("搜索词写入失败：{e:?}");
+        }
+        match get_keyword_list(true, 1, HOT_SEARCH_MAX).await {
+            Ok((_, rows)) => HOT_SEARCH.store(Arc::new(rows)),
+            Err(e) => eprintln!("热门搜索刷新失败：{e:?}"),
+        }
+    }
+}
+
+/// 把待写入的搜索词合并后写入数据库
+async fn flush_keywords() -> Result<(), DbErr> {
+    if !get_config().search.is_record {
+        return Ok(());
+    }
+    let Some(redis) = get_redis_conn().await else {
+        return Ok(());
+    };
+    let pending: HashMap<String, u64> = {
+        let mut conn = redis.lock().await;
+        // 上次写入失败时 FLUSH_KEY 还在 先把它处理完
+        let _: Result<bool, _> = conn.rename_nx(PENDING_KEY, FLUSH_KEY).await;
+        conn.hgetall(FLUSH_KEY).await.unwrap_or_default()
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut counts: HashMap<String, (u64, u64)> = HashMap::new();
+    for (field, num) in pending {
+        let Some((hit, keyword)) = field.split_once('|') else { continue; };
+        let entry = counts.entry(keyword.to_string()).or_default();
+        if hit == "1" {
+            entry.0 += num;
+        } else {
+            entry.1 += num;
+        }
+    }
+    let now = timestamp_10();
+    let rows: Vec<(String, (u64, u64))> = counts.into_iter().collect();
+    for chunk in rows.chunks(BATCH_ROWS) {
+        let sql = format!(
+            "INSERT INTO {}article_searchlog (keyword, searchnum, hitnum, missnum, lasttime) VALUES {} ON DUPLICATE KEY UPDATE searchnum = searchnum + VALUES(searchnum), hitnum = hitnum + VALUES(hitnum), missnum = missnum + VALUES(missnum), lasttime = VALUES(lasttime)",
+            get_config().prefix,
+            vec!["(?, ?, ?, ?, ?)"; chunk.len()].join(", ")
+        );
+        let mut values: Vec<Value> = Vec::with_capacity(chunk.len() * 5);
+        for (keyword, (hit, miss)) in chunk {
+            values.push(keyword.clone().into());
+            values.push((hit + miss).into());
+            values.push((*hit).into());
+            values.push((*miss).into());
+            values.push(now.into());
+        }
+        exec_sql(sql.as_str(), Some(Values(values))).await?;
+    }
+    let mut conn = redis.lock().await;
+    let _: Result<(), _> = conn.del(FLUSH_KEY).await;
+    Ok(())
+}
+
+/// 按书名或作者搜索小说 返回 (总数, 当前页) 按 search.time 缓存
+///
+/// 页码超出时返回空列表
+pub(crate) async fn search_novels(url: &str, keyword: &str, page: u64, limit: u64) -> (u64, Vec<Novel>) {
+    let search_key = Value::String(Some(keyword.to_string()));
+    let (search_where, search_values) = if get_config().sys_ver > 6.0f32 {    // 多选搜索兼容
+        ("MATCH(articlename, author) AGAINST(CONCAT('+',?) IN BOOLEAN MODE)", vec![search_key.clone()])
+    } else {
+        ("(articlename LIKE CONCAT('%',?, '%') OR author LIKE CONCAT('%',?, '%'))", vec![search_key.clone(), search_key.clone()])
+    };
+    let count = get_cache_count(
+        format!("SELECT COUNT(*) AS cnt FROM {table}article_article WHERE {where} AND {search_where};", table = get_config().prefix, where = get_config().get_where(), search_where = search_where),
+        url,
+        get_config().search.time as u64,
+        Some(Values(search_values.clone())),
+    ).await;
+    let offset = page.saturating_sub(1).saturating_mul(limit);
+    if count <= offset {
+        return (count, vec![]);
+    }
+    let mut row_values = search_values;
+    row_values.push(offset.into());
+    let rows = get_cache_rows(
+        format!("SELECT {filed} FROM {table}article_article WHERE {where} AND {search_where} ORDER BY lastupdate DESC LIMIT {limit} OFFSET ?;", filed = get_config().get_field(), table = get_config().prefix, where = get_config().get_where(), search_where = search_where, limit = limit),
+        url,
+        get_config().search.time as u64,
+        Some(Values(row_values)),
+    ).await;
+    (count, rows)
+}
+
+/// 分页取搜索词 返回 (总数, 当前页)
+///
+/// hit 为 true 时按搜索次数取有结果的关键字 否则取从未搜到结果的关键字
+pub(crate) async fn get_keyword_list(hit: bool, page: u64, per_page: u64) -> Result<(u64, Vec<SearchKeyword>), DbErr> {
+    let table = format!("{}article_searchlog", get_config().prefix);
+    let where_sql = if hit { "hitnum > 0" } else { "hitnum = 0" };
+    let count = query_count(
+        format!("SELECT COUNT(*) AS cnt FROM {} WHERE {}", table, where_sql).as_str(),
+        None,
+    ).await?;
+    let offset = page.saturating_sub(1).saturating_mul(per_page);
+    let mut rows: Vec<SearchKeyword> = query_all_as(
+        format!("SELECT keyword, searchnum, hitnum, missnum, lasttime FROM {} WHERE {} ORDER BY searchnum DESC LIMIT ? OFFSET ?", table, where_sql).as_str(),
+        Some(Values(vec![per_page.into(), offset.into()])),
+    ).await?;
+    for row in rows.iter_mut() {
+        row.url = get_config().search_url(&row.keyword, 1);
+    }
+    Ok((count, rows))
+}
@@ -0,0 +1,186 @@
+use axum::http::HeaderMap;
+use axum_extra::extract::CookieJar;
+use cookie::Cookie;
+use rand::Rng;
+use sea_orm::{DbErr, Values};
+use subtle::ConstantTimeEq;
+use crate::models::user::{User, UserSession};
+use crate::services::password::is_jieqi_hash;
+use crate::services::user::{get_user_by_id, timestamp_10};
+use crate::utils::conf::get_config;
+use crate::utils::cookie::{set_cookie_value, set_session_cookie};
+use crate::utils::db::db::{exec_sql, get_one_as, query_all_as};
+use crate::utils::redis::conn::{cache_del, cache_get_json, cache_set_json, get_redis_conn};
+use crate::utils::request::{client_ip, is_https};
+
+/// 保存会话 token 的 cookie
+pub(crate) const SESSION_COOKIE: &str = "ss_session";
+/// 距上次顺延超过这个秒数才再次顺延 避免每个请求都写库
+const SESSION_TOUCH: u64 = 60;
+
+fn session_key(token: &str) -> String {
+    format!("novel:session:{}", token)
+}
+
+fn session_ttl() -> u64 {
+    get_config().session.days.max(1) as u64 * 86400
+}
+
+/// token 为 64 位十六进制 其它格式不查库
+fn is_valid_token(token: &str) -> bool {
+    token.len() == 64 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// 新建会话 同时清理已过期的会话
+pub(crate) async fn create_session(user: &User, headers: &HeaderMap) -> Result<UserSession, DbErr> {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill(&mut bytes);
+    let now = timestamp_10() as u64;
+    let ua = headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let session = UserSession {
+        token: hex::encode(bytes),
+        userid: user.uid,
+        uname: user.uname.clone(),
+        ip: client_ip(headers).chars().take(64).collect(),
+        ua: ua.chars().take(255).collect(),
+        created: now,
+        lastvisit: now,
+        expire: now + session_ttl(),
+    };
+    let table = format!("{}user_session", get_config().prefix);
+    exec_sql(
+        format!("DELETE FROM {} WHERE expire < ?", table).as_str(),
+        Some(Values(vec![now.into()])),
+    ).await?;
+    exec_sql(
+        format!("INSERT INTO {} (token, userid, uname, ip, ua, created, lastvisit, expire) VALUES (?, ?, ?, ?, ?, ?, ?, ?)", table).as_str(),
+        Some(Values(vec![
+            session.token.clone().into(),
+            session.userid.into(),
+            session.uname.clone().into(),
+            session.ip.clone().into(),
+            session.ua.clone().into(),
+            session.created.into(),
+            session.lastvisit.into(),
+            session.expire.into(),
+        ])),
+    ).await?;
+    let _ = cache_set_json(get_redis_conn().await.as_ref(), &session_key(&session.token), &session, session_ttl()).await;
+    Ok(session)
+}
+
+/// 取有效的会话 先查 Redis 未命中再查库 有访问时顺延过期时间
+pub(crate) async fn get_session(token: &str) -> Option<UserSession> {
+    if !is_valid_token(token) {
+        return None;
+    }
+    let now = timestamp_10() as u64;
+    let redis = get_redis_conn().await;
+    let key = session_key(token);
+    let mut session = match cache_get_json::<UserSession>(redis.as_ref(), &key).await {
+        Ok(Some(session)) => session,
+        _ => {
+            let sql = format!(
+                "SELECT token, userid, uname, ip, ua, created, lastvisit, expire FROM {}user_session WHERE token = ? LIMIT 1",
+                get_config().prefix
+            );
+            get_one_as::<UserSession>(&sql, Some(Values(vec![token.into()])))
+                .await
+                .inspect_err(|e| eprintln!("get_session error: {e:?}"))
+                .ok()??
+        }
+    };
+    if session.expire <= now {
+        return None;
+    }
+    if now.saturating_sub(session.lastvisit) >= SESSION_TOUCH {
+        session.lastvisit = now;
+        session.expire = now + session_ttl();
+        let sql = format!("UPDATE {}user_session SET lastvisit = ?, expire = ? WHERE token = ?", get_config().prefix);
+        if let Err(e) = exec_sql(&sql, Some(Values(vec![session.lastvisit.into(), session.expire.into(), token.into()]))).await {
+            eprintln!("touch session error: {e:?}");
+        }
+        let _ = cache_set_json(redis.as_ref(), &key, &session, session_ttl()).await;
+    }
+    Some(session)
+}
+
+/// 用户未过期的会话 最近访问的在前
+pub(crate) async fn list_sessions(user_id: u64) -> Result<Vec<UserSession>, DbErr> {
+    let sql = format!(
+        "SELECT token, userid, uname, ip, ua, created, lastvisit, expire FROM {}user_session WHERE userid = ? AND expire > ? ORDER BY lastvisit DESC",
+        get_config().prefix
+    );
+    query_all_as(&sql, Some(Values(vec![user_id.into(), (timestamp_10() as u64).into()]))).await
+}
+
+/// 删除一个会话
+pub(crate) async fn delete_session(token: &str) -> Result<(), DbErr> {
+    let sql = format!("DELETE FROM {}user_session WHERE token = ?", get_config().prefix);
+    exec_sql(&sql, Some(Values(vec![token.into()]))).await?;
+    cache_del(&[session_key(token)]).await;
+    Ok(())
+}
+
+/// 删除用户的全部会话 即在所有设备上退出 返回删除的数量
+pub(crate) async fn delete_user_sessions(user_id: u64) -> Result<u64, DbErr> {
+    let sql = format!("SELECT token, userid, uname, ip, ua, created, lastvisit, expire FROM {}user_session WHERE userid = ?", get_config().prefix);
+    let sessions = query_all_as::<UserSession>(&sql, Some(Values(vec![user_id.into()]))).await?;
+    let sql = format!("DELETE FROM {}user_session WHERE userid = ?", get_config().prefix);
+    let num = exec_sql(&sql, Some(Values(vec![user_id.into()]))).await?;
+    let keys: Vec<String> = sessions.iter().map(|s| session_key(&s.token)).collect();
+    cache_del(&keys).await;
+    Ok(num)
+}
+
+/// 登录成功后写入 cookie ss_userid/ss_username 只给页面脚本显示用 服务端不信任
+pub(crate) fn login_cookies(mut jar: CookieJar, headers: &HeaderMap, session: &UserSession) -> CookieJar {
+    let age = session_ttl() as usize;
+    jar = set_session_cookie(jar, SESSION_COOKIE, &session.token, age, is_https(headers));
+    jar = set_cookie_value(jar, "ss_userid", session.userid.to_string().as_str(), age, false, false);
+    jar = set_cookie_value(jar, "ss_username", session.uname.as_str(), age, false, false);
+    jar.remove(Cookie::build("ss_password").path("/").build())
+}
+
+/// 清除登录相关的 cookie
+pub(crate) fn logout_cookies(jar: CookieJar) -> CookieJar {
+    jar.remove(Cookie::build(SESSION_COOKIE).path("/").build())
+        .remove(Cookie::build("ss_userid").path("/").build())
+        .remove(Cookie::build("ss_username").path("/").build())
+        .remove(Cookie::build("ss_password").path("/").build())
+}
+
+/// 取当前登录的会话 兼容期内旧版 ss_userid/ss_password cookie 校验通过后换成会话
+///
+/// 返回的 jar 可能写入了新会话 需要随响应返回
+pub(crate) async fn current_session(jar: CookieJar, headers: &HeaderMap) -> (CookieJar, Option<UserSession>) {
+    if let Some(token) = jar.get(SESSION_COOKIE).map(|c| c.value().to_string()) {
+        if let Some(session) = get_session(&token).await {
+            return (jar, Some(session));
+        }
+        return (logout_cookies(jar), None);
+    }
+    if !get_config().session.legacy_cookie {
+        return (jar, None);
+    }
+    let user_id = jar.get("ss_userid").map(|c| c.value().to_string());
+    let pass = jar.get("ss_password").map(|c| c.value().to_string());
+    let (Some(user_id), Some(pass)) = (user_id, pass) else {
+        return (jar, None);
+    };
+    let user = match get_user_by_id(&user_id).await {
+        // 只有杰奇格式的密码才可能出现在旧 cookie 里 按常量时间比较
+        Ok(user) if is_jieqi_hash(&user.pass) && bool::from(user.pass.as_bytes().ct_eq(pass.as_bytes())) => user,
+        _ => return (logout_cookies(jar), None),
+    };
+    match create_session(&user, headers).await {
+        Ok(session) => (login_cookies(jar, headers, &session), Some(session)),
+        Err(e) => {
+            eprintln!("create_session error: {e:?}");
+            (jar, None)
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use sea_orm::{DbErr, FromQueryResult, Values};
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{query_all_as, query_count};
+use crate::utils::redis::conn::{cache_key, get_cache_text};
+use crate::utils::text::{time_to_w3c, xml_escape};
+
+/// 每个子 sitemap 最多的链接数 协议上限是 50000 这里取小一些减轻单次查询
+pub(crate) const SITEMAP_PAGE_SIZE: u64 = 5000;
+
+#[derive(Debug, FromQueryResult)]
+struct SitemapRow {
+    id: u64,
+    subid: u64,
+    lastmod: u64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct SortRow {
+    sortid: u64,
+    cnt: i64,
+    lastmod: u64,
+}
+
+/// 子 sitemap 的种类 长尾词未启用时不输出
+fn sitemap_kinds() -> Vec<&'static str> {
+    let mut kinds = vec!["novel", "sort", "chapter"];
+    if get_config().is_lang {
+        kinds.push("lang");
+    }
+    kinds
+}
+
+pub(crate) fn sitemap_url(base: &str, kind: &str, page: u64) -> String {
+    format!("{}/sitemap/{}/{}.xml", base, kind, page)
+}
+
+/// sitemap 索引 列出所有子 sitemap
+pub(crate) async fn get_sitemap_index(base: &str) -> Result<String, DbErr> {
+    cached(base, "index", 0, || async {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for kind in sitemap_kinds() {
+            let total = count_urls(kind).await?;
+            let pages = total.div_ceil(SITEMAP_PAGE_SIZE);
+            for page in 1..=pages {
+                xml.push_str(&format!("<sitemap><loc>{}</loc></sitemap>\n", xml_escape(&sitemap_url(base, kind, page))));
+            }
+        }
+        xml.push_str("</sitemapindex>\n");
+        Ok(xml)
+    }).await
+}
+
+/// 子 sitemap 种类不存在或页码超出时返回 None
+pub(crate) async fn get_sitemap(base: &str, kind: &str, page: u64) -> Result<Option<String>, DbErr> {
+    if page == 0 || !sitemap_kinds().contains(&kind) {
+        return Ok(None);
+    }
+    let xml = cached(base, kind, page, || async {
+        let urls = get_urls(kind, page).await?;
+        if urls.is_empty() {
+            return Ok(String::new());
+        }
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for (loc, lastmod) in urls {
+            xml.push_str(&format!("<url><loc>{}</loc>", xml_escape(&format!("{}{}", base, loc))));
+            if lastmod > 0 {
+                xml.push_str(&format!("<lastmod>{}</lastmod>", time_to_w3c(lastmod as i64)));
+            }
+            xml.push_str("</url>\n");
+        }
+        xml.push_str("</urlset>\n");
+        Ok(xml)
+    }).await?;
+    Ok(if xml.is_empty() { None } else { Some(xml) })
+}
+
+/// 按 cache.other 缓存生成好的 XML 空结果也缓存 避免反复查询不存在的页
+async fn cached<F, Fut>(base: &str, kind: &str, page: u64, build: F) -> Result<String, DbErr>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<String, DbErr>>,
+{
+    let key = cache_key("sitemap", base, kind, &Some(Values(vec![page.into()])));
+    get_cache_text(&key, get_config().cache.other as u64, build).await
+}
+
+async fn count_urls(kind: &str) -> Result<u64, DbErr> {
+    let prefix = get_config().prefix.clone();
+    let where_sql = get_config().get_where();
+    match kind {
+        "novel" => query_count(
+            format!("SELECT COUNT(*) AS cnt FROM {}article_article WHERE {}", prefix, where_sql).as_str(),
+            None,
+        ).await,
+        "chapter" => query_count(
+            format!("SELECT COUNT(*) AS cnt FROM {}article_article WHERE {} AND lastchapterid > 0", prefix, where_sql).as_str(),
+            None,
+        ).await,
+        "lang" => query_count(
+            format!("SELECT COUNT(*) AS cnt FROM {p}article_langtail WHERE sourceid IN (SELECT articleid FROM {p}article_article WHERE {w})", p = prefix, w = where_sql).as_str(),
+            None,
+        ).await,
+        "sort" => Ok(get_sort_urls().await?.len() as u64),
+        _ => Ok(0),
+    }
+}
+
+/// 取某一页的 (相对链接, 最后更新时间)
+async fn get_urls(kind: &str, page: u64) -> Result<Vec<(String, u64)>, DbErr> {
+    let conf = get_config();
+    let offset = (page - 1).saturating_mul(SITEMAP_PAGE_SIZE);
+    if kind == "sort" {
+        return Ok(get_sort_urls().await?
+            .into_iter()
+            .skip(offset as usize)
+            .take(SITEMAP_PAGE_SIZE as usize)
+            .collect());
+    }
+    let sql = match kind {
+        "novel" => format!(
+            "SELECT articleid AS id, lastchapterid AS subid, lastupdate AS lastmod FROM {}article_article WHERE {} ORDER BY articleid LIMIT ? OFFSET ?",
+            conf.prefix, conf.get_where()
+        ),
+        "chapter" => format!(
+            "SELECT articleid AS id, lastchapterid AS subid, lastupdate AS lastmod FROM {}article_article WHERE {} AND lastchapterid > 0 ORDER BY lastupdate DESC LIMIT ? OFFSET ?",
+            conf.prefix, conf.get_where()
+        ),
+        "lang" => format!(
+            "SELECT langid AS id, sourceid AS subid, uptime AS lastmod FROM {p}article_langtail WHERE sourceid IN (SELECT articleid FROM {p}article_article WHERE {w}) ORDER BY langid LIMIT ? OFFSET ?",
+            p = conf.prefix, w = conf.get_where()
+        ),
+        _ => return Ok(vec![]),
+    };
+    let rows: Vec<SitemapRow> = query_all_as(
+        sql.as_str(),
+        Some(Values(vec![SITEMAP_PAGE_SIZE.into(), offset.into()])),
+    ).await?;
+    Ok(rows.into_iter().map(|row| {
+        let loc = match kind {
+            "novel" => conf.info_url(conf.new_id(row.id)),
+            "chapter" => conf.read_url(conf.new_id(row.id), conf.new_id(row.subid), 1),
+            _ => conf.lang_info_url(conf.new_id(row.id)),
+        };
+        (loc, row.lastmod)
+    }).collect())
+}
+
+/// 分类页每一页都列出 数量很少 一次全部算出
+async fn get_sort_urls() -> Result<Vec<(String, u64)>, DbErr> {
+    let conf = get_config();
+    let rows: Vec<SortRow> = query_all_as(
+        format!(
+            "SELECT CAST(sortid AS UNSIGNED) AS sortid, COUNT(*) AS cnt, CAST(MAX(lastupdate) AS UNSIGNED) AS lastmod FROM {}article_article WHERE {} GROUP BY sortid",
+            conf.prefix, conf.get_where()
+        ).as_str(),
+        None,
+    ).await?;
+    let per_page = (conf.category_per_page as u64).max(1);
+    let mut urls = Vec::new();
+    for (i, sort) in conf.sort_arr.iter().enumerate() {
+        let sort_id = i as u64 + 1;
+        let (cnt, lastmod) = rows
+            .iter()
+            .find(|r| r.sortid == sort_id)
+            .map(|r| (r.cnt.max(0) as u64, r.lastmod))
+            .unwrap_or((0, 0));
+        let pages = cnt.div_ceil(per_page).max(1);
+        for page in 1..=pages {
+            urls.push((conf.sort_url(&sort.code, i + 1, page as usize), lastmod));
+        }
+    }
+    Ok(urls)
+}
@@ -1,9 +1,10 @@
-use sea_orm::{DbErr, Value, Values};
+use std::cmp::Reverse;
+use sea_orm::{DbErr, FromQueryResult, Value, Values};
 use thiserror::Error;
 use time::OffsetDateTime;
-use uuid::Uuid;
 use crate::models::user::{BookShelf, BookShelfOnNovel, User};
-use crate::services::novel::query_novel_process;
+use crate::services::novel::{get_chapter_rows, query_novel_process};
+use crate::services::password::{hash_password, jieqi_hash, jieqi_salt, set_password_hash, verify_user_password, UNUSABLE_PASS};
 use crate::utils::conf::get_config;
 use crate::utils::db::db::{exec_sql, get_one_as, query_all_as, query_count};
 
@@ -48,7 +49,13 @@ pub(crate) async fn create_user(
         return Err(CreateUserError::EmailAlreadyExist);
     }
     let regdate = timestamp_10();
-    let pass = format!("{:x}", md5::compute(password.as_bytes()));
+    // 与 PHP 杰奇共用数据库时仍写杰奇格式的密码 否则只写 Argon2 哈希 先算好哈希再插入
+    let salt = jieqi_salt();
+    let (pass, hash) = if get_config().jieqi_password {
+        (jieqi_hash(password, &salt), None)
+    } else {
+        (UNUSABLE_PASS.to_string(), Some(hash_password(password).await.ok_or(CreateUserError::Unknown)?))
+    };
     let (sql, values) = if get_config().sys_ver < 2.0 {
         (
             format!(
@@ -64,11 +71,6 @@ pub(crate) async fn create_user(
             ]),
         )
     } else {
-        let u = Uuid::new_v4().to_string();
-        let md5_hex = format!("{:x}", md5::compute(u.as_bytes()));
-        let salt = md5_hex[md5_hex.len() - 16..].to_string();
-        let raw = format!("{}{}", pass, salt);
-        let pass_1 = format!("{:x}", md5::compute(raw.as_bytes()));
         (
             format!(
                 "INSERT INTO {table}system_users (uname, name, pass, email, regdate, salt) VALUES (?, ?, ?, ?, ?, ?)",
@@ -77,7 +79,7 @@ pub(crate) async fn create_user(
             Values(vec![
                 Value::String(Some(username.to_owned())),
                 Value::String(Some(username.to_owned())),
-                Value::String(Some(pass_1)),
+                Value::String(Some(pass)),
                 Value::String(Some(email.to_owned())),
                 Value::BigInt(Some(regdate)),
                 Value::String(Some(salt)),
@@ -85,73 +87,66 @@ pub(crate) async fn create_user(
         )
     };
     let num = exec_sql(
-        &*sql,
+        &sql,
         Option::from(values)
     ).await?;
     if num == 0 {
         return Err(CreateUserError::InsertFailed);
     }
-    get_user(username, password).await
+    let user = find_user("uname", username).await?.ok_or(CreateUserError::InsertFailed)?;
+    if let Some(hash) = hash
+        && let Err(e) = set_password_hash(user.uid, &hash).await
+    {
+        // 没有哈希的账号无法登录 删掉后用户名可以重新注册
+        let sql = format!("DELETE FROM {}system_users WHERE uid = ?", get_config().prefix);
+        if let Err(e) = exec_sql(&sql, Some(Values(vec![user.uid.into()]))).await {
+            eprintln!("delete user error: {e:?}");
+        }
+        return Err(e.into());
+    }
+    Ok(user)
 }
 
-pub async fn is_user_login(
-    user_id: &str,
-    password: &str,
-) -> Result<bool, CreateUserError> {
+/// 按 uid uname 或 email 取用户 column 只能传这三个
+async fn find_user(column: &str, value: &str) -> Result<Option<User>, DbErr> {
     let sql = if get_config().sys_ver == 1.7 {
         format!(
-            "SELECT *,'' AS salt FROM {table}system_users WHERE uid = ? LIMIT 1",
+            "SELECT *,'' AS salt FROM {table}system_users WHERE {column} = ? LIMIT 1",
             table = get_config().prefix
         )
     } else {
         format!(
-            "SELECT * FROM {table}system_users WHERE uid = ? LIMIT 1",
+            "SELECT * FROM {table}system_users WHERE {column} = ? LIMIT 1",
             table = get_config().prefix
         )
     };
-    let user = get_one_as::<User>(
+    get_one_as::<User>(
         &sql,
-        Some(Values(vec![Value::String(Some(user_id.to_owned()))])),
-    )
+        Some(Values(vec![Value::String(Some(value.to_owned()))])),
+    ).await
+}
+
+pub async fn get_user_by_id(
+    user_id: &str,
+) -> Result<User, CreateUserError> {
+    find_user("uid", user_id)
         .await?
-        .ok_or(CreateUserError::LoginAuthFailed)?;
-    if user.pass != password {
-        return Err(CreateUserError::LoginAuthFailed);
-    }
-    Ok(true)
+        .ok_or(CreateUserError::LoginAuthFailed)
+}
+
+/// 按邮箱取用户 找回密码用
+pub(crate) async fn get_user_by_email(email: &str) -> Result<Option<User>, DbErr> {
+    find_user("email", email).await
 }
 
 pub async fn get_user(
     username: &str,
     password: &str,
 ) -> Result<User, CreateUserError> {
-    let sql = if get_config().sys_ver == 1.7 {
-        format!(
-            "SELECT *,'' AS salt FROM {table}system_users WHERE uname = ? LIMIT 1",
-            table = get_config().prefix
-        )
-    } else {
-        format!(
-            "SELECT * FROM {table}system_users WHERE uname = ? LIMIT 1",
-            table = get_config().prefix
-        )
-    };
-    let user = get_one_as::<User>(
-        &sql,
-        Some(Values(vec![Value::String(Some(username.to_owned()))])),
-    )
+    let user = find_user("uname", username)
         .await?
         .ok_or(CreateUserError::UserNotExist)?;
-    let pass0 = format!("{:x}", md5::compute(password.as_bytes()));
-    let expected = if get_config().sys_ver < 2.0 {
-        pass0
-    } else {
-        let salt = user.salt.clone();
-        let raw = format!("{}{}", pass0, salt);
-        format!("{:x}", md5::compute(raw.as_bytes()))
-    };
-
-    if user.pass != expected {
+    if !verify_user_password(&user, password).await? {
         return Err(CreateUserError::PasswordError);
     }
     Ok(user)
@@ -163,8 +158,38 @@ pub fn timestamp_10() -> i64 {
     OffsetDateTime::now_utc().unix_timestamp()
 }
 
+/// 书架排序
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BookcaseOrder {
+    /// 小说最近更新
+    Update,
+    /// 最近阅读
+    Read,
+    /// 有更新的在前 未读章节多的在前
+    Unread,
+}
+
+impl BookcaseOrder {
+    pub(crate) fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "update" => Some(Self::Update),
+            "read" => Some(Self::Read),
+            "unread" => Some(Self::Unread),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ReadTimeRow {
+    articleid: u64,
+    readtime: u64,
+}
+
 pub async fn get_bookcase_list(
+    url: &str,
     user_id: &str,
+    order: BookcaseOrder,
 ) -> Result<Vec<BookShelfOnNovel>, DbErr> {
     let sql = format!(
         "SELECT * FROM {table}article_bookcase WHERE userid = ?",
@@ -186,24 +211,63 @@ pub async fn get_bookcase_list(
         .take(bs.len())
         .collect::<Vec<_>>()
         .join(",");
-    let novel_sql = format!("SELECT * FROM {table}article_article WHERE articleid IN ({}) ORDER BY lastupdate DESC",placeholders,table = get_config().prefix);
+    let novel_sql = format!("SELECT * FROM {table}article_article WHERE {where} AND articleid IN ({}) ORDER BY lastupdate DESC",placeholders,table = get_config().prefix,where = get_config().get_where());
     let novel_list = query_novel_process(
         &novel_sql,
-        Some(Values(vals)),
+        Some(Values(vals.clone())),
     ).await?;
-    let ret: Vec<BookShelfOnNovel> = bs
+    let time_sql = format!("SELECT articleid, readtime FROM {table}article_readlog WHERE userid = ? AND articleid IN ({})",placeholders,table = get_config().prefix);
+    let mut time_vals = vec![Value::String(Some(user_id.to_owned()))];
+    time_vals.extend(vals);
+    let read_times = query_all_as::<ReadTimeRow>(&time_sql, Some(Values(time_vals))).await?;
+    let mut ret: Vec<BookShelfOnNovel> = bs
         .into_iter()
         .filter_map(|b| {
+            let readtime = read_times.iter().find(|t| t.articleid == b.articleid).map(|t| t.readtime).unwrap_or(0);
             novel_list
                 .iter()
                 .find(|n| n.source_id == b.articleid)
                 .cloned()
-                .map(|novel| BookShelfOnNovel { case: b, novel })
+                .map(|novel| BookShelfOnNovel {
+                    // 章节ID不一定按目录顺序递增 书签不在最新章节就算有更新
+                    has_update: novel.lastchapterid != 0 && novel.lastchapterid != b.chapterid,
+                    unread: 0,
+                    readtime,
+                    case: b,
+                    novel,
+                })
         })
         .collect();
+    for item in ret.iter_mut().filter(|item| item.has_update) {
+        let chapter_rows = get_chapter_rows(url, get_config().cache.info, item.novel.source_id).await;
+        // 目录按 chapterorder 排序 书签之后的都算未读 书签不在目录里时全部未读
+        let start = chapter_rows
+            .iter()
+            .position(|c| c.source_id == item.case.chapterid)
+            .map_or(0, |i| i + 1);
+        // chaptertype = 1 为分卷 不是章节
+        item.unread = chapter_rows[start..]
+            .iter()
+            .filter(|c| c.chaptertype != 1)
+            .count() as u64;
+    }
+    match order {
+        BookcaseOrder::Update => ret.sort_by_key(|i| Reverse(i.novel.lastupdate)),
+        BookcaseOrder::Read => ret.sort_by_key(|i| Reverse(i.readtime)),
+        BookcaseOrder::Unread => ret.sort_by_key(|i| Reverse((i.has_update, i.unread, i.novel.lastupdate))),
+    }
     Ok(ret)
 }
 
+/// 书架中有更新的小说数 只看书签是不是最新章节 不读取章节列表
+pub(crate) async fn get_bookcase_update_count(user_id: &str) -> Result<u64, DbErr> {
+    let sql = format!(
+        "SELECT COUNT(*) AS cnt FROM {table}article_bookcase b INNER JOIN {table}article_article a ON a.articleid = b.articleid WHERE b.userid = ? AND a.display <> 1 AND a.lastchapterid <> 0 AND a.lastchapterid <> b.chapterid",
+        table = get_config().prefix
+    );
+    query_count(&sql, Some(Values(vec![Value::String(Some(user_id.to_owned()))]))).await
+}
+
 
 pub(crate) fn bookshelf_mapping(bs: &mut [BookShelf]) {
     for b in bs {
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use axum::http::HeaderMap;
+use axum::http::header::USER_AGENT;
+use chrono::{Datelike, Local, NaiveDate};
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use regex::Regex;
+use sea_orm::{DbErr, Value, Values};
+use tokio::sync::Mutex;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::exec_sql;
+use crate::utils::redis::conn::get_redis_conn;
+use crate::utils::request::client_fingerprint;
+
+/// 待写入数据库的点击数 字段为小说原始ID
+const PENDING_KEY: &str = "novel:visit:pending";
+/// 正在写入数据库的点击数 写入失败会保留到下次重试
+const FLUSH_KEY: &str = "novel:visit:flush";
+/// 上次清零日/周/月点击的日期 多个实例共用
+const ROLLOVER_KEY: &str = "novel:visit:rollover";
+/// 同一客户端对同一本书在这个时间内只计一次
+const DEDUPE_SECS: u64 = 3600;
+/// 写入数据库的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// 每条 UPDATE 语句最多更新的小说数
+const BATCH_ROWS: usize = 500;
+
+static BOT_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)bot|spider|crawl|slurp|curl|wget|python|java/|go-http|httpclient|okhttp|scrapy|headless|phantomjs|lighthouse")
+        .expect("正则编译失败")
+});
+
+/// Redis 不可用时记录上次清零的日期
+static LAST_ROLLOVER: Lazy<Mutex<Option<NaiveDate>>> = Lazy::new(|| Mutex::new(None));
+
+/// 记录一次点击 只写 Redis 由后台任务批量写入数据库
+///
+/// 爬虫和空 User-Agent 不计数 Redis 不可用时不记录
+pub(crate) async fn record_visit(headers: &HeaderMap, source_id: u64) {
+    let ua = headers
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if ua.is_empty() || BOT_REGEX.is_match(ua) {
+        return;
+    }
+    let Some(redis) = get_redis_conn().await else {
+        return;
+    };
+    let seen_key = format!("novel:visit:seen:{}:{}", client_fingerprint(headers), source_id);
+    let mut conn = redis.lock().await;
+    let opts = redis::SetOptions::default()
+        .conditional_set(redis::ExistenceCheck::NX)
+        .with_expiration(redis::SetExpiry::EX(DEDUPE_SECS));
+    let first: Result<Option<String>, _> = conn.set_options(&seen_key, 1, opts).await;
+    if matches!(first, Ok(None)) {
+        return;
+    }
+    let _: Result<i64, _> = conn.hincr(PENDING_KEY, source_id, 1).await;
+}
+
+/// 后台任务 定时把点击数写入数据库 跨天后清零日/周/月点击和推荐
+pub(crate) async fn visit_task() {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = flush_visits().await {
+            eprintln!("点击数写入失败：{e:?}");
+        }
+        if let Err(e) = rollover().await {
+            eprintln!("点击数清零失败：{e:?}");
+        }
+    }
+}
+
+/// 把待写入的点击数按数量分组 每组一条 UPDATE
+async fn flush_visits() -> Result<(), DbErr> {
+    let Some(redis) = get_redis_conn().await else {
+        return Ok(());
+    };
+    let pending: HashMap<u64, u64> = {
+        let mut conn = redis.lock().await;
+        // 上次写入失败时 FLUSH_KEY 还在 先把它处理完
+        let _: Result<bool, _> = conn.rename_nx(PENDING_KEY, FLUSH_KEY).await;
+        conn.hgetall(FLUSH_KEY).await.unwrap_or_default()
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut groups: HashMap<u64, Vec<u64>> = HashMap::new();
+    for (source_id, num) in pending {
+        groups.entry(num).or_default().push(source_id);
+    }
+    for (num, ids) in groups {
+        for chunk in ids.chunks(BATCH_ROWS) {
+            let sql = format!(
+                "UPDATE {}article_article SET allvisit = allvisit + ?, monthvisit = monthvisit + ?, weekvisit = weekvisit + ?, dayvisit = dayvisit + ? WHERE articleid IN ({})",
+                get_config().prefix,
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut values: Vec<Value> = vec![num.into(), num.into(), num.into(), num.into()];
+            values.extend(chunk.iter().map(|id| Value::from(*id)));
+            exec_sql(sql.as_str(), Some(Values(values))).await?;
+        }
+    }
+    let mut conn = redis.lock().await;
+    let _: Result<(), _> = conn.del(FLUSH_KEY).await;
+    Ok(())
+}
+
+/// 跨天清零日点击和日推荐 跨周(周一)清零周数据 跨月清零月数据
+///
+/// 与杰奇定时任务一致 第一次运行只记录日期不清零 避免重启时误清
+async fn rollover() -> Result<(), DbErr> {
+    let today = Local::now().date_naive();
+    let last = match get_redis_conn().await {
+        Some(redis) => {
+            let mut conn = redis.lock().await;
+            let old: Option<String> = conn
+                .getset(ROLLOVER_KEY, today.format("%Y-%m-%d").to_string())
+                .await
+                .unwrap_or(None);
+            old.and_then(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok())
+        }
+        None => LAST_ROLLOVER.lock().await.replace(today),
+    };
+    let Some(last) = last else {
+        return Ok(());
+    };
+    if last >= today {
+        return Ok(());
+    }
+    let mut columns = vec!["dayvisit = 0", "dayvote = 0"];
+    if last.iso_week() != today.iso_week() {
+        columns.push("weekvisit = 0");
+        columns.push("weekvote = 0");
+    }
+    if (last.year(), last.month()) != (today.year(), today.month()) {
+        columns.push("monthvisit = 0");
+        columns.push("monthvote = 0");
+    }
+    let sql = format!("UPDATE {}article_article SET {}", get_config().prefix, columns.join(", "));
+    if let Err(e) = exec_sql(sql.as_str(), None).await {
+        // 清零失败时把日期改回去 下次重试
+        match get_redis_conn().await {
+            Some(redis) => {
+                let mut conn = redis.lock().await;
+                let _: Result<(), _> = conn.set(ROLLOVER_KEY, last.format("%Y-%m-%d").to_string()).await;
+            }
+            None => *LAST_ROLLOVER.lock().await = Some(last),
+        }
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use chrono::Local;
+use redis::AsyncCommands;
+use sea_orm::{Value, Values};
+use thiserror::Error;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::{exec_sql, query_count};
+use crate::utils::redis::conn::get_redis_conn;
+
+#[derive(Error, Debug)]
+pub enum VoteError {
+    #[error("管理员已关闭推荐票功能")]
+    Disabled,
+    #[error("今日推荐票已用完")]
+    QuotaExceeded,
+    #[error("小说不存在")]
+    NovelNotExist,
+    #[error("数据库错误：{0}")]
+    DbErr(#[from] sea_orm::error::DbErr),
+}
+
+/// 投一张推荐票 返回今日剩余票数
+///
+/// 先占用当日额度再更新小说 更新失败会把额度退回
+pub(crate) async fn add_vote(user_id: u64, source_id: u64) -> Result<u32, VoteError> {
+    let perday = get_config().vote_perday;
+    if perday <= 0 {
+        return Err(VoteError::Disabled);
+    }
+    let perday = perday as u32;
+    let date = Local::now().format("%Y%m%d").to_string().parse::<u32>().unwrap_or(0);
+    let used = take_quota(user_id, date, perday).await?;
+    let sql = format!(
+        "UPDATE {}article_article SET allvote = allvote + 1, dayvote = dayvote + 1, weekvote = weekvote + 1, monthvote = monthvote + 1 WHERE articleid = ?",
+        get_config().prefix
+    );
+    match exec_sql(&sql, Some(Values(vec![Value::BigUnsigned(Some(source_id))]))).await {
+        Ok(n) if n > 0 => Ok(perday.saturating_sub(used)),
+        Ok(_) => {
+            refund_quota(user_id, date).await;
+            Err(VoteError::NovelNotExist)
+        }
+        Err(e) => {
+            refund_quota(user_id, date).await;
+            Err(VoteError::DbErr(e))
+        }
+    }
+}
+
+fn vote_key(user_id: u64, date: u32) -> String {
+    format!("novel:vote:{}:{}", date, user_id)
+}
+
+/// 占用一张票 返回今日已用票数
+async fn take_quota(user_id: u64, date: u32, perday: u32) -> Result<u32, VoteError> {
+    if let Some(redis) = get_redis_conn().await {
+        let key = vote_key(user_id, date);
+        let mut conn = redis.lock().await;
+        if let Ok(used) = conn.incr::<_, _, i64>(&key, 1).await {
+            if used == 1 {
+                let _: Result<bool, _> = conn.expire(&key, 86400 * 2).await;
+            }
+            if used > perday as i64 {
+                let _: Result<i64, _> = conn.decr(&key, 1).await;
+                return Err(VoteError::QuotaExceeded);
+            }
+            return Ok(used as u32);
+        }
+    }
+    // Redis 不可用时 用带条件的 UPDATE 保证并发下不会超额
+    let table = format!("{}article_uservote", get_config().prefix);
+    let n = exec_sql(
+        format!("UPDATE {} SET num = num + 1 WHERE userid = ? AND votedate = ? AND num < ?", table).as_str(),
+        Some(Values(vec![user_id.into(), date.into(), perday.into()])),
+    ).await?;
+    if n == 0 {
+        let n = exec_sql(
+            format!("INSERT IGNORE INTO {} (userid, votedate, num) VALUES (?, ?, 1)", table).as_str(),
+            Some(Values(vec![user_id.into(), date.into()])),
+        ).await?;
+        if n == 0 {
+            return Err(VoteError::QuotaExceeded);
+        }
+        return Ok(1);
+    }
+    let used = query_count(
+        format!("SELECT CAST(num AS SIGNED) AS cnt FROM {} WHERE userid = ? AND votedate = ?", table).as_str(),
+        Some(Values(vec![user_id.into(), date.into()])),
+    ).await?;
+    Ok(used as u32)
+}
+
+async fn refund_quota(user_id: u64, date: u32) {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        if conn.decr::<_, _, i64>(vote_key(user_id, date), 1).await.is_ok() {
+            return;
+        }
+    }
+    let _ = exec_sql(
+        format!("UPDATE {}article_uservote SET num = num - 1 WHERE userid = ? AND votedate = ? AND num > 0", get_config().prefix).as_str(),
+        Some(Values(vec![user_id.into(), date.into()])),
+    ).await;
+}
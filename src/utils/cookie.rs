@@ -17,6 +17,18 @@ pub fn set_cookie_value(jar: CookieJar, key: &str, value: &str,age: usize,only:
     jar.add(cookie)
 }
 
+/// 会话 cookie 只给服务端读 不跨站携带
+pub fn set_session_cookie(jar: CookieJar, key: &str, value: &str, age: usize, secure: bool) -> CookieJar {
+    let cookie = Cookie::build((key.to_owned(), value.to_owned()))
+        .path("/")
+        .max_age(Duration::seconds(age as i64))
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Strict)
+        .build();
+    jar.add(cookie)
+}
+
 #[allow(dead_code)]
 pub fn remove_cookie(jar: CookieJar, key: &str) -> CookieJar {
     jar.remove(Cookie::from(key.to_owned()))
@@ -1,2 +1,3 @@
 pub mod db;
-pub mod conn;
\ No newline at end of file
+pub mod conn;
+pub mod schema;
\ No newline at end of file
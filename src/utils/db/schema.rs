@@ -0,0 +1,101 @@
+use sea_orm::DbErr;
+use crate::utils::conf::get_config;
+use crate::utils::db::db::exec_sql;
+
+/// 本程序新增的表 杰奇原有的表不在这里创建
+///
+/// {prefix} 会被替换为配置中的表前缀
+const TABLES: &[&str] = &[
+    // 推荐票每日额度 Redis 不可用时使用
+    "CREATE TABLE IF NOT EXISTS {prefix}article_uservote (
+        userid INT UNSIGNED NOT NULL,
+        votedate INT UNSIGNED NOT NULL,
+        num SMALLINT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (userid, votedate)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 章节报错 status 0待处理 1已处理 2已忽略
+    "CREATE TABLE IF NOT EXISTS {prefix}article_report (
+        reportid INT UNSIGNED NOT NULL AUTO_INCREMENT,
+        articleid INT UNSIGNED NOT NULL DEFAULT 0,
+        articlename VARCHAR(100) NOT NULL DEFAULT '',
+        chapterid INT UNSIGNED NOT NULL DEFAULT 0,
+        chaptername VARCHAR(100) NOT NULL DEFAULT '',
+        reason VARCHAR(255) NOT NULL DEFAULT '',
+        fingerprint CHAR(32) NOT NULL DEFAULT '',
+        host VARCHAR(100) NOT NULL DEFAULT '',
+        reporttime INT UNSIGNED NOT NULL DEFAULT 0,
+        status TINYINT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (reportid),
+        KEY status (status, reportid)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 搜索词统计 由 Redis 批量写入
+    "CREATE TABLE IF NOT EXISTS {prefix}article_searchlog (
+        keyword VARCHAR(60) NOT NULL,
+        searchnum INT UNSIGNED NOT NULL DEFAULT 0,
+        hitnum INT UNSIGNED NOT NULL DEFAULT 0,
+        missnum INT UNSIGNED NOT NULL DEFAULT 0,
+        lasttime INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (keyword),
+        KEY searchnum (searchnum)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 阅读记录 每个用户每本书一条 与浏览器本地记录合并
+    "CREATE TABLE IF NOT EXISTS {prefix}article_readlog (
+        userid INT UNSIGNED NOT NULL,
+        articleid INT UNSIGNED NOT NULL,
+        chapterid INT UNSIGNED NOT NULL DEFAULT 0,
+        chaptername VARCHAR(100) NOT NULL DEFAULT '',
+        readtime INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (userid, articleid),
+        KEY readtime (userid, readtime)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 登录会话 Redis 不可用或缓存过期时从这里读取
+    "CREATE TABLE IF NOT EXISTS {prefix}user_session (
+        token CHAR(64) NOT NULL,
+        userid INT UNSIGNED NOT NULL,
+        uname VARCHAR(50) NOT NULL DEFAULT '',
+        ip VARCHAR(64) NOT NULL DEFAULT '',
+        ua VARCHAR(255) NOT NULL DEFAULT '',
+        created INT UNSIGNED NOT NULL DEFAULT 0,
+        lastvisit INT UNSIGNED NOT NULL DEFAULT 0,
+        expire INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (token),
+        KEY userid (userid),
+        KEY expire (expire)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // Argon2id 密码哈希 杰奇的 system_users.pass 保持原样
+    "CREATE TABLE IF NOT EXISTS {prefix}user_password (
+        userid INT UNSIGNED NOT NULL,
+        hash VARCHAR(255) NOT NULL DEFAULT '',
+        updated INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (userid)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 已验证的邮箱 与 system_users.email 相同才算已验证
+    "CREATE TABLE IF NOT EXISTS {prefix}user_email (
+        userid INT UNSIGNED NOT NULL,
+        email VARCHAR(100) NOT NULL DEFAULT '',
+        verified INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (userid)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+    // 后台操作记录 uid 0 为用 ADMIN_TOKEN 进入
+    "CREATE TABLE IF NOT EXISTS {prefix}admin_log (
+        logid INT UNSIGNED NOT NULL AUTO_INCREMENT,
+        uid INT UNSIGNED NOT NULL DEFAULT 0,
+        uname VARCHAR(50) NOT NULL DEFAULT '',
+        action VARCHAR(30) NOT NULL DEFAULT '',
+        articleid INT UNSIGNED NOT NULL DEFAULT 0,
+        detail VARCHAR(255) NOT NULL DEFAULT '',
+        ip VARCHAR(64) NOT NULL DEFAULT '',
+        logtime INT UNSIGNED NOT NULL DEFAULT 0,
+        PRIMARY KEY (logid),
+        KEY articleid (articleid, logid)
+    ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+];
+
+/// 启动时创建缺失的表 已存在则跳过
+pub async fn init_schema() -> Result<(), DbErr> {
+    let prefix = get_config().prefix.clone();
+    for sql in TABLES {
+        exec_sql(&sql.replace("{prefix}", &prefix), None).await?;
+    }
+    Ok(())
+}
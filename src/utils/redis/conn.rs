@@ -97,14 +97,48 @@ pub(crate) async fn cache_get_json<T: DeserializeOwned>(
     Ok(s.and_then(|s| serde_json::from_str(&s).ok()))
 }
 
+/// 查询缓存的键 由域名、语句和参数共同决定
+pub(crate) fn cache_key(kind: &str, url: &str, sql: &str, value: &Option<Values>) -> String {
+    let key_seed = format!("{}|{}|{:?}", url, sql, value);
+    format!("novel:{}:{:x}", kind, md5::compute(key_seed))
+}
+
+/// 删除缓存 Redis 不可用时什么都不做
+pub(crate) async fn cache_del(keys: &[String]) {
+    if keys.is_empty() {
+        return;
+    }
+    let Some(redis) = get_redis_conn().await else {
+        return;
+    };
+    let mut conn = redis.lock().await;
+    let _: RedisResult<()> = conn.del(keys).await;
+}
+
+/// 缓存生成好的文本 如 sitemap、Atom 等 XML 未命中时调用 build 生成
+///
+/// 生成失败不写缓存 Redis 不可用时每次都生成
+pub(crate) async fn get_cache_text<F, Fut, E>(key: &str, cache_time: u64, build: F) -> Result<String, E>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<String, E>>,
+{
+    let redis = get_redis_conn().await;
+    if let Ok(Some(text)) = cache_get_json::<String>(redis.as_ref(), key).await {
+        return Ok(text);
+    }
+    let text = build().await?;
+    let _ = cache_set_json(redis.as_ref(), key, &text, cache_time).await;
+    Ok(text)
+}
+
 pub(crate) async fn get_cache_count(
     sql: String,
     url: &str,
     cache_time: u64,
     value: Option<Values>,
 ) -> u64 {
-    let key_seed = format!("{}|{}|{:?}", url, sql, value);
-    let key = format!("novel:count:{:x}", md5::compute(key_seed));
+    let key = cache_key("count", url, &sql, &value);
     let redis = get_redis_conn().await;
     if let Some(ref redis_arc) = redis {
         if let Ok(Some(cnt)) = cache_get_json::<u64>(Some(redis_arc), &key).await {
@@ -127,8 +161,7 @@ pub(crate) async fn get_cache_rows(
     cache_time: u64,
     value: Option<Values>,
 ) -> Vec<Novel> {
-    let key_seed = format!("{}|{}|{:?}", url, sql, value);
-    let key = format!("novel:rows:{:x}", md5::compute(key_seed));
+    let key = cache_key("rows", url, &sql, &value);
     let redis = get_redis_conn().await;
     if let Some(ref redis_arc) = redis {
         if let Ok(Some(rows)) = cache_get_json::<Vec<Novel>>(Some(redis_arc), &key).await {
@@ -152,8 +185,7 @@ pub(crate) async fn get_chapter_rows(
     cache_time: u64,
     value: Option<Values>,
 ) -> Vec<NovelChapter> {
-    let key_seed = format!("{}|{}|{:?}", url, sql, value);
-    let key = format!("novel:chapters:{:x}", md5::compute(key_seed));
+    let key = cache_key("chapters", url, &sql, &value);
     let redis = get_redis_conn().await;
     if let Some(ref redis_arc) = redis {
         if let Ok(Some(rows)) = cache_get_json::<Vec<NovelChapter>>(Some(redis_arc), &key).await {
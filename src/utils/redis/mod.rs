@@ -1 +1,2 @@
-pub mod conn;
\ No newline at end of file
+pub mod conn;
+pub mod store;
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use redis::{AsyncCommands, RedisResult};
+use tokio::sync::Mutex;
+use crate::services::user::timestamp_10;
+use crate::utils::redis::conn::get_redis_conn;
+
+/// 带有效期的临时数据 如验证码答案、邮件 token、发送间隔
+///
+/// 先存 Redis Redis 不可用或出错时存内存 内存里的只在当前进程内有效 值为 (内容, 过期时间)
+static MEMORY: Lazy<Mutex<HashMap<String, (String, u64)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub(crate) async fn set_value(key: &str, value: &str, ttl: u64) {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<()> = conn.set_ex(key, value, ttl).await;
+        if res.is_ok() {
+            return;
+        }
+    }
+    let now = timestamp_10() as u64;
+    let mut memory = MEMORY.lock().await;
+    memory.retain(|_, (_, expire)| *expire > now);
+    memory.insert(key.to_string(), (value.to_string(), now + ttl));
+}
+
+/// 键不存在时才写入 写入成功返回 true
+pub(crate) async fn set_value_nx(key: &str, value: &str, ttl: u64) -> bool {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<bool> = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl)
+            .query_async(&mut *conn)
+            .await;
+        if let Ok(ok) = res {
+            return ok;
+        }
+    }
+    let now = timestamp_10() as u64;
+    let mut memory = MEMORY.lock().await;
+    memory.retain(|_, (_, expire)| *expire > now);
+    if memory.contains_key(key) {
+        return false;
+    }
+    memory.insert(key.to_string(), (value.to_string(), now + ttl));
+    true
+}
+
+pub(crate) async fn get_value(key: &str) -> Option<String> {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<Option<String>> = conn.get(key).await;
+        if let Ok(value) = res {
+            return value;
+        }
+    }
+    let now = timestamp_10() as u64;
+    MEMORY.lock().await.get(key).filter(|(_, expire)| *expire > now).map(|(v, _)| v.clone())
+}
+
+/// 取出并删除
+pub(crate) async fn take_value(key: &str) -> Option<String> {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<Option<String>> = conn.get_del(key).await;
+        if let Ok(value) = res {
+            return value;
+        }
+    }
+    let now = timestamp_10() as u64;
+    MEMORY.lock().await.remove(key).filter(|(_, expire)| *expire > now).map(|(v, _)| v)
+}
+
+/// 计数加一 返回新的次数 有效期从第一次计数开始算
+pub(crate) async fn incr_value(key: &str, ttl: u64) -> u32 {
+    if let Some(redis) = get_redis_conn().await {
+        let mut conn = redis.lock().await;
+        let res: RedisResult<u32> = conn.incr(key, 1).await;
+        if let Ok(count) = res {
+            if count == 1 {
+                let _: RedisResult<()> = conn.expire(key, ttl as i64).await;
+            }
+            return count;
+        }
+    }
+    let now = timestamp_10() as u64;
+    let mut memory = MEMORY.lock().await;
+    memory.retain(|_, (_, expire)| *expire > now);
+    let entry = memory.entry(key.to_string()).or_insert_with(|| ("0".to_string(), now + ttl));
+    let count = entry.0.parse::<u32>().unwrap_or(0) + 1;
+    entry.0 = count.to_string();
+    count
+}
@@ -1,4 +1,9 @@
-use std::{io::Read, sync::Arc, time::Duration};
+use std::{io::Read, net::{IpAddr, SocketAddr}, sync::Arc, time::Duration};
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
 
 use encoding_rs::{Encoding, GB18030, GBK, UTF_8};
 use encoding_rs_io::DecodeReaderBytesBuilder;
@@ -9,6 +14,7 @@ use reqwest::StatusCode;
 use thiserror::Error;
 use tokio::sync::Semaphore;
 use url::Url;
+use crate::utils::conf::get_config;
 
 pub(crate) static FETCHER: Lazy<HttpFetcher> = Lazy::new(|| {
     HttpFetcher::new(30, 800).expect("init HttpFetcher failed")
@@ -153,6 +159,24 @@ impl HttpFetcher {
         Err(HttpRequestError::ReadBodyError("unexpected retry loop exit".into()))
     }
 
+    /// 获取二进制内容 (封面图片等)
+    pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>, HttpRequestError> {
+        let parsed = parse_http_url(url)?;
+        let _permit = self.global_sem.clone().acquire_owned().await.unwrap();
+        for attempt in 0..=self.retry_times {
+            let resp = self.client.get(parsed.clone()).send().await?;
+            if resp.status().is_success() {
+                return Ok(resp.bytes().await?.to_vec());
+            }
+            let st = resp.status();
+            if attempt < self.retry_times && Self::should_retry(st) {
+                self.backoff_sleep(attempt).await;
+                continue;
+            }
+            return Err(HttpRequestError::NonSuccessStatusCode(st));
+        }
+        Err(HttpRequestError::ReadBodyError("unexpected retry loop exit".into()))
+    }
 
     #[allow(dead_code)]
     pub async fn post_json_text<T: serde::Serialize>(
@@ -177,3 +201,146 @@ impl HttpFetcher {
         Err(HttpRequestError::ReadBodyError("unexpected retry loop exit".into()))
     }
 }
+
+/// 反向代理转发时带的请求头 只有来自 TRUSTED_PROXIES 的请求才保留
+const FORWARDED_HEADERS: [&str; 3] = ["x-forwarded-for", "x-real-ip", "x-forwarded-proto"];
+
+/// .env 的 TRUSTED_PROXIES 英文逗号分隔 支持 IP 和 CIDR 未配置时只信任本机
+static TRUSTED_PROXIES: Lazy<Vec<(IpAddr, u8)>> = Lazy::new(|| {
+    let list = std::env::var("TRUSTED_PROXIES").unwrap_or_else(|_| "127.0.0.1,::1".to_string());
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| {
+            let parsed = match s.split_once('/') {
+                Some((ip, bits)) => ip.parse::<IpAddr>().ok().zip(bits.parse::<u8>().ok()),
+                None => s.parse::<IpAddr>().ok().map(|ip| (ip, if ip.is_ipv4() { 32 } else { 128 })),
+            };
+            if parsed.is_none() {
+                eprintln!("TRUSTED_PROXIES 格式错误：{}", s);
+            }
+            parsed
+        })
+        .collect()
+});
+
+fn is_trusted_proxy(ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    TRUSTED_PROXIES.iter().any(|(net, bits)| match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - (*bits).min(32) as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(*net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - (*bits).min(128) as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(*net) & mask
+        }
+        _ => false,
+    })
+}
+
+/// 按连接地址确定客户端IP 写入 X-Real-IP 后面统一用 client_ip 读取
+///
+/// 连接来自 TRUSTED_PROXIES 时 从 X-Forwarded-For 右边往左取第一个不是代理的地址 没有时用代理传的 X-Real-IP
+/// 否则丢弃客户端自己带的转发请求头 直接用连接地址
+pub(crate) async fn real_ip(mut req: Request, next: Next) -> Response {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip().to_canonical());
+    let trusted = peer.is_some_and(is_trusted_proxy);
+    let headers = req.headers_mut();
+    let ip = if trusted {
+        headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.rsplit(',')
+                    .filter_map(|s| s.trim().parse::<IpAddr>().ok())
+                    .find(|ip| !is_trusted_proxy(*ip))
+            })
+            .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse().ok()))
+            .or(peer)
+    } else {
+        for name in FORWARDED_HEADERS {
+            headers.remove(name);
+        }
+        peer
+    };
+    headers.remove("x-forwarded-for");
+    if let Some(ip) = ip
+        && let Ok(value) = HeaderValue::from_str(&ip.to_string())
+    {
+        headers.insert("x-real-ip", value);
+    }
+    next.run(req).await
+}
+
+/// 取客户端IP 由 real_ip 中间件按 TRUSTED_PROXIES 确定
+///
+/// 取不到时返回空字符串
+pub(crate) fn client_ip(headers: &axum::http::HeaderMap) -> String {
+    headers
+        .get("x-real-ip")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// 客户端指纹 IP 加 User-Agent 的 MD5
+pub(crate) fn client_fingerprint(headers: &axum::http::HeaderMap) -> String {
+    let ua = headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    format!("{:x}", md5::compute(format!("{}|{}", client_ip(headers), ua)))
+}
+
+/// 请求的 Host 作为缓存键的一部分 取不到时返回 unknown.host
+pub(crate) fn request_host(headers: &axum::http::HeaderMap) -> &str {
+    headers
+        .get(axum::http::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown.host")
+}
+
+/// 站点根地址 用于生成绝对链接 末尾不带 /
+///
+/// 优先用配置的 site_url 未配置时用请求的 Host
+pub(crate) fn site_base_url(headers: &axum::http::HeaderMap) -> String {
+    let site_url = get_config().site_url.trim().trim_end_matches('/').to_string();
+    if !site_url.is_empty() {
+        return site_url;
+    }
+    let host = headers
+        .get(axum::http::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| *v == "https")
+        .unwrap_or("http");
+    format!("{}://{}", scheme, host)
+}
+
+/// 可以写进缓存或邮件的站点根地址 末尾不带 /
+///
+/// 优先用配置的 site_url 未配置时只接受 domains 里列出的 Host 其它 Host 返回 None
+pub(crate) fn trusted_base_url(headers: &axum::http::HeaderMap) -> Option<String> {
+    let site_url = get_config().site_url.trim().trim_end_matches('/').to_string();
+    if !site_url.is_empty() {
+        return Some(site_url);
+    }
+    let host = request_host(headers);
+    get_config()
+        .domains
+        .iter()
+        .any(|d| d.trim().eq_ignore_ascii_case(host))
+        .then(|| site_base_url(headers))
+}
+
+/// 请求是否走 HTTPS 反向代理需要传 X-Forwarded-Proto 未传时看 site_url
+pub(crate) fn is_https(headers: &axum::http::HeaderMap) -> bool {
+    match headers.get("x-forwarded-proto").and_then(|v| v.to_str().ok()) {
+        Some(proto) => proto.eq_ignore_ascii_case("https"),
+        None => get_config().site_url.trim().starts_with("https://"),
+    }
+}
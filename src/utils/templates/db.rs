@@ -23,7 +23,7 @@ pub struct RawNovel;
 ///
 /// # 参数
 ///
-/// - `sql`: sql语句 {table}表示表名 {field}表示查询字段
+/// - `sql`: sql语句 {table}表示表名 {field}表示查询字段 {where}表示前台显示条件(不含隐藏的小说)
 /// - `cache`: 是否缓存这个结果 (bool类型 可选 默认true)
 /// - `url`: 传入当前调用host 也就是传 SITE_URL 标签
 ///
@@ -48,7 +48,7 @@ impl Function for RawNovel {
             .transpose()?
             .unwrap_or(true);
         let table = format!("{}article_article",get_config().prefix);
-        let sqlx = sql.replace("{table}", &*table).replace("{field}",get_config().get_field().as_str());
+        let sqlx = sql.replace("{table}", &*table).replace("{field}",get_config().get_field().as_str()).replace("{where}",get_config().get_where().as_str());
         let rows = TOKIO_RT
             .block_on(async move {
                 if cache {
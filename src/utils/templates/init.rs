@@ -3,7 +3,7 @@ use std::sync::Arc;
 use tera::{Result as TeraResult, Tera};
 use crate::utils::db::conn::{DB_CONN};
 use crate::utils::templates::db::{DbQueryTag, RawNovel};
-use crate::utils::templates::str::{AdsFunction, GETConfigFunction, LinkFunction, RandomStringFunction, RewriterFunction, SortArrayFunction, StatCodeFunction, SubstrFunction, TimeFunction};
+use crate::utils::templates::str::{AdsFunction, CaptchaFunction, GETConfigFunction, HotSearchFunction, LinkFunction, RandomStringFunction, RewriterFunction, SortArrayFunction, StatCodeFunction, SubstrFunction, TimeFunction};
 
 /// --------------------------
 /// 初始化Tera模板引擎（全局复用）
@@ -24,6 +24,8 @@ pub fn init_tera() -> TeraResult<Arc<Tera>> {
     tera.register_function("link",LinkFunction);
     tera.register_function("raw_novel",RawNovel);
     tera.register_function("random_string",RandomStringFunction);
+    tera.register_function("hot_search",HotSearchFunction);
+    tera.register_function("captcha",CaptchaFunction);
     Ok(Arc::new(tera))
 }
 
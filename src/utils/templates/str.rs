@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use chrono::{Local, TimeZone};
 use tera::{Function, Result as TeraResult, Value};
+use crate::services::captcha;
+use crate::services::search::get_hot_search;
 use crate::utils::conf::get_config;
 use rand::prelude::*;
 
-const RANDOM_CHARS: [char; 36] = [
+pub(crate) const RANDOM_CHARS: [char; 36] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
     'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
     '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'
@@ -17,6 +19,18 @@ const RANDOM_LETTER_CHARS: [char; 62] = [
     '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
 ];
 
+/// 从 chars 中随机取 length 个字符
+pub(crate) fn random_string(chars: &[char], length: usize) -> String {
+    let mut rng = rand::rng();
+    let mut result = String::with_capacity(length);
+    for _ in 0..length {
+        if let Some(c) = chars.choose(&mut rng) {
+            result.push(*c);
+        }
+    }
+    result
+}
+
 /// 随机数
 ///
 /// # 用法
@@ -35,23 +49,54 @@ impl Function for RandomStringFunction {
             .map(|v| v.as_u64().ok_or(tera::Error::msg("length 必须是number类型")))
             .transpose()?
             .unwrap_or(5) as usize;
-        let mut rng = rand::rng();
-        let mut result = String::with_capacity(length);
-        if letter {
-            for _ in 0..length {
-                let c = RANDOM_LETTER_CHARS.choose(&mut rng).unwrap();
-                result.push(*c);
-            }
+        let result = if letter {
+            random_string(&RANDOM_LETTER_CHARS, length)
         } else {
-            for _ in 0..length {
-                let c = RANDOM_CHARS.choose(&mut rng).unwrap();
-                result.push(*c);
-            }
-        }
+            random_string(&RANDOM_CHARS, length)
+        };
         Ok(Value::String(result))
     }
 }
 
+/// 图片验证码 输出验证码图片和隐藏的 captcha_id 字段 点击图片换一张
+///
+/// # 用法
+/// - `form` register 或 login 后台没有开启该表单的验证码时输出空字符串 (不传时总是输出)
+///
+/// 模板里还需要一个 name 为 captcha 的输入框
+pub struct CaptchaFunction;
+impl Function for CaptchaFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let form = args
+            .get("form")
+            .map(|v| v.as_str().ok_or(tera::Error::msg("form 必须是string类型")))
+            .transpose()?
+            .unwrap_or("");
+        if !captcha::is_required(form) {
+            return Ok(Value::String(String::new()));
+        }
+        Ok(Value::String(captcha::captcha_html()))
+    }
+}
+
+/// 热门搜索 数据由后台任务每分钟刷新一次
+///
+/// # 用法
+/// - `limit` 返回条数 (默认10 最多50)
+///
+/// 返回数组 每项包含 keyword(关键字) searchnum(搜索次数) url(搜索链接)
+pub struct HotSearchFunction;
+impl Function for HotSearchFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let limit = args
+            .get("limit")
+            .map(|v| v.as_u64().ok_or(tera::Error::msg("limit 必须是number类型")))
+            .transpose()?
+            .unwrap_or(10) as usize;
+        serde_json::to_value(get_hot_search(limit)).map_err(|e| tera::Error::msg(e.to_string()))
+    }
+}
+
 pub struct LinkFunction;
 impl Function for LinkFunction {
     fn call(&self, _args: &HashMap<String, Value>) -> TeraResult<Value> {
@@ -93,6 +138,9 @@ impl Function for GETConfigFunction {
         // 出于性能考虑 此处不做任何序列化取值 默认返回空字符串
         match key {
             "is_lang" => Ok(Value::Bool(get_config().is_lang)),
+            "enable_down" => Ok(Value::Bool(get_config().enable_down)),
+            "is_report" => Ok(Value::Bool(get_config().is_report)),
+            "vote_perday" => Ok(Value::Number(get_config().vote_perday.into())),
             _ => Ok(Value::String("".to_string())),
         }
     }
@@ -104,7 +152,7 @@ impl Function for RewriterFunction {
     fn call(&self, args: &HashMap<String, Value>) -> TeraResult<Value> {
         let type_str = args
             .get("type")
-            .ok_or_else(|| tera::Error::msg("获取哪种类型的伪静态是必须的(search | rank | top)"))?
+            .ok_or_else(|| tera::Error::msg("获取哪种类型的伪静态是必须的(search | rank | top | history | complete)"))?
             .as_str()
             .ok_or_else(|| tera::Error::msg("type 参数必须是字符串类型"))?;
         match type_str {
@@ -114,10 +162,46 @@ impl Function for RewriterFunction {
                     .get("code")
                     .and_then(|v| v.as_str())
                     .unwrap_or("allvisit");
-                Ok(Value::String(get_config().rank_url(code)))
+                let sort = args
+                    .get("sort")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(crate::handlers::rank::RANK_ALL);
+                let page = args
+                    .get("page")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(1) as usize;
+                Ok(Value::String(crate::handlers::rank::rank_link(code, sort, page)))
             },
             "top" => Ok(Value::String(get_config().rewrite.top_url.clone())),
             "history" => Ok(Value::String(get_config().rewrite.history_url.clone())),
+            "complete" => {
+                let sort = args
+                    .get("sort")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(crate::handlers::complete::COMPLETE_ALL);
+                Ok(Value::String(get_config().complete_url(sort, 1)))
+            },
+            "down" => {
+                let id = args
+                    .get("id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| tera::Error::msg("down 类型需要传入 id 参数"))?;
+                Ok(Value::String(get_config().down_url(id)))
+            },
+            "epub" => {
+                let id = args
+                    .get("id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| tera::Error::msg("epub 类型需要传入 id 参数"))?;
+                Ok(Value::String(get_config().epub_url(id)))
+            },
+            "feed" => {
+                // 不传 id 为全站更新 传入 id 为单本小说的章节更新
+                match args.get("id").and_then(|v| v.as_u64()) {
+                    Some(id) => Ok(Value::String(get_config().novel_feed_url(id))),
+                    None => Ok(Value::String(get_config().rewrite.feed_url.clone())),
+                }
+            },
             _ => Err(tera::Error::msg(format!("未知的 type 参数值: {}", type_str))),
         }
     }
@@ -121,3 +121,28 @@ pub fn time_to_cn(time: i64) -> String {
         dt.format("%Y-%m-%d").to_string()
     }
 }
+
+/// XML 转义 引号也要转义 用于属性值
+pub fn xml_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// 时间戳转 W3C 时间格式 如 2024-01-02T03:04:05+00:00 用于 sitemap 和 Atom
+pub fn time_to_w3c(time: i64) -> String {
+    let dt = match Utc.timestamp_opt(time, 0) {
+        chrono::LocalResult::Single(dt) => dt,
+        _ => Utc::now(),
+    };
+    dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}